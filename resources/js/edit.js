@@ -17,14 +17,19 @@ if (localStorage.getItem("jwt")) {
 }
 
 
+const visibilitySelect = document.getElementById("visibility");
+visibilitySelect.value = visibilitySelect.dataset.value;
+
 function get_edit_content(){
     let title = document.getElementById("title").value;
     console.log(title);
     let description = document.getElementById("description").value;
     console.log(description)
+    let visibility = visibilitySelect.value;
+    let tags = document.getElementById("tags").value.split(",").map((tag) => tag.trim()).filter((tag) => tag);
     let rx = /([^\/]*)\/edit/g;
     let id = rx.exec(document.URL)[1];
-    fetch("/" + id, {method:"POST", body: JSON.stringify({title, description}), headers: {"Content-Type":"application/json", "Authorization": localStorage.getItem("JWT")}})
+    fetch("/" + id, {method:"POST", body: JSON.stringify({title, description, tags, visibility}), headers: {"Content-Type":"application/json", "Authorization": localStorage.getItem("JWT")}})
 }
 
 
@@ -42,9 +47,12 @@ function getSongs() {
         let selections = document.getElementById("selections");
         selections.innerHTML = "";
         for (let i = 0; i < res.length; i++) {
+            let song = res[i];
             let option = new Option();
-            option.value = res[i];
-            option.innerText = res[i];
+            option.value = song.name;
+            option.innerText = song.title
+                ? (song.artist ? song.artist + " - " : "") + song.title
+                : song.name;
             selections.add(option);
         }
     }))
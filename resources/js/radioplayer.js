@@ -36,3 +36,28 @@ buttonPlay.addEventListener("click", () => {
 audio.addEventListener("error", () => {
   console.error("An error occurred during audio playback.");
 });
+
+// Show the song currently on air
+const nowPlaying = document.getElementById("now-playing");
+const radioPath = window.location.pathname.replace(/\/(index\.html)?$/, "");
+// Private radios need the share token of the page on every request
+const share = new URLSearchParams(window.location.search).get("share");
+function withShare(url) {
+  return share ? url + (url.includes("?") ? "&" : "?") + "share=" + encodeURIComponent(share) : url;
+}
+const cover = document.getElementById("cover");
+cover.addEventListener("load", () => (cover.hidden = false));
+cover.addEventListener("error", () => (cover.hidden = true));
+function showNowPlaying(now) {
+  cover.src = withShare(radioPath + "/now/cover?song=" + encodeURIComponent(now.song || ""));
+  nowPlaying.innerText = now.song
+    ? "Now playing: " + now.song + (now.next ? " \u2013 Next: " + now.next : "")
+    : "";
+}
+fetch(withShare(radioPath + "/now"))
+  .then((res) => res.json())
+  .then(showNowPlaying)
+  .catch((error) => console.error(error));
+new EventSource(withShare(radioPath + "/events")).addEventListener("now-playing", (event) =>
+  showNowPlaying(JSON.parse(event.data))
+);
@@ -25,7 +25,8 @@ function addRadio() {
         },
         body: JSON.stringify({
             title: document.getElementById("radio-title").value,
-            description: document.getElementById("radio-description").value
+            description: document.getElementById("radio-description").value,
+            tags: document.getElementById("radio-tags").value.split(",").map(tag => tag.trim()).filter(tag => tag)
         })
     })
         .then(response => response.json())
@@ -1,5 +1,6 @@
 use actix::Response;
-use actix_web::{FromRequest, HttpResponse};
+use actix_web::cookie::{time, Cookie, SameSite};
+use actix_web::{FromRequest, HttpRequest, HttpResponse};
 use futures::io::Empty;
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
 use openidconnect::reqwest::async_http_client;
@@ -24,10 +25,12 @@ use std::collections::HashMap;
 use std::env;
 use std::fmt::Debug;
 use std::future::{ready, Ready};
+use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Instant;
 
 use crate::errors::PageError;
+use crate::members::Role;
 use crate::AppState;
 
 pub struct OidcClient {
@@ -57,10 +60,50 @@ pub struct RedirectResponse {
     pub scope: String,
 }
 
+/// What a user may do on the whole site, each role may do all the lower ones can
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GlobalRole {
+    #[default]
+    User,
+    /// Edits any radio, like its editors
+    Moderator,
+    /// Does anything with any radio, like its owner
+    Admin,
+}
+
+impl GlobalRole {
+    /// The role on every radio this gives, `None` for only the radios one is a member of
+    pub fn radio_role(&self) -> Option<Role> {
+        match self {
+            Self::User => None,
+            Self::Moderator => Some(Role::Editor),
+            Self::Admin => Some(Role::Owner),
+        }
+    }
+}
+
+impl FromStr for GlobalRole {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "user" => Ok(Self::User),
+            "moderator" => Ok(Self::Moderator),
+            "admin" => Ok(Self::Admin),
+            _ => Err(format!(
+                "Unknown role {s}, expected user, moderator or admin"
+            )),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: SubjectIdentifier,
     pub exp: usize,
+    /// The role when the token was issued, for showing; the stored one is checked
+    #[serde(default)]
+    pub role: GlobalRole,
 }
 
 #[derive(Debug, Clone)]
@@ -139,6 +182,14 @@ pub fn decode_token(token: &str, oidc_client: &OidcClient) -> Option<SubjectIden
         .map(|x| x.claims.sub)
 }
 
+/// Cookie with the token, only read to personalise pages (it's sent cross-site on links)
+pub const TOKEN_COOKIE: &str = "jwt";
+
+/// The user a page is requested by, if logged in
+pub fn cookie_sub(req: &HttpRequest, oidc_client: &OidcClient) -> Option<SubjectIdentifier> {
+    decode_token(req.cookie(TOKEN_COOKIE)?.value(), oidc_client)
+}
+
 #[routes]
 #[get("/auth/google/start")]
 #[get("/auth/google/start/")]
@@ -217,24 +268,35 @@ pub async fn google_callback(
 
     let sub = claims.subject().clone();
 
+    let role = state
+        .users
+        .write()
+        .await
+        .entry(sub.clone())
+        .or_default()
+        .role;
+
+    let lifetime = 7 * 24 * 60 * 60;
     let token = encode(
         &state.oidc_client.header,
         &Claims {
             sub: sub.clone(),
-            exp: jsonwebtoken::get_current_timestamp() as usize + 7 * 24 * 60 * 60,
+            exp: jsonwebtoken::get_current_timestamp() as usize + lifetime,
+            role,
         },
         &state.oidc_client.encoding_key,
     )
     .map_err(|_| PageError::InternalError)?;
 
-    state
-        .users
-        .write()
-        .await
-        .entry(sub.clone())
-        .or_insert(vec![]);
+    let cookie = Cookie::build(TOKEN_COOKIE, token.clone())
+        .path("/")
+        .secure(true)
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .max_age(time::Duration::seconds(lifetime as i64))
+        .finish();
 
-    Ok(HttpResponse::Ok().body(format!(
+    Ok(HttpResponse::Ok().cookie(cookie).body(format!(
         "
         <!DOCTYPE html>
         <html>
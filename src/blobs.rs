@@ -0,0 +1,322 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{create_dir_all, read_dir, rename, write},
+    io,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use sha2::{Digest, Sha256};
+
+use crate::{storage::Storage, SongId};
+
+/// Directory of the blobs, inside the data directory
+pub const BLOBS_DIR: &str = "blobs";
+/// File in a song directory naming its blob
+const BLOB_REF: &str = "blob";
+
+#[derive(Debug, Default)]
+struct BlobIndex {
+    /// Number of songs referencing each blob
+    refs: HashMap<String, usize>,
+    /// Blob of each song of each radio
+    songs: HashMap<String, HashMap<SongId, String>>,
+    /// Blobs still being written
+    pending: HashSet<String>,
+    /// Length in seconds of the blobs read so far
+    lens: HashMap<String, f64>,
+}
+
+/// Transcoded songs, stored once per content and shared between radios
+///
+/// Each blob lives in `blobs/{hash}` and holds the segments and `len` of a song,
+/// songs reference it through the `blob` key in `{radio}/{song}`.
+#[derive(Debug, Clone)]
+pub struct Blobs {
+    storage: Arc<dyn Storage>,
+    index: Arc<Mutex<BlobIndex>>,
+}
+
+/// Hash identifying the blob of some decoded audio
+pub fn content_hash(pcm: &[i16]) -> String {
+    let mut hasher = Sha256::new();
+    for sample in pcm {
+        hasher.update(sample.to_le_bytes());
+    }
+    hex::encode(hasher.finalize())
+}
+
+fn blob_key(hash: &str) -> String {
+    format!("{BLOBS_DIR}/{hash}")
+}
+
+/// Key prefix of the blob of a song, read from its reference
+pub fn song_blob_key(storage: &dyn Storage, radio: &str, song: SongId) -> io::Result<String> {
+    Ok(blob_key(
+        &storage.read_to_string(&format!("{radio}/{song}/{BLOB_REF}"))?,
+    ))
+}
+
+impl Blobs {
+    /// Load the references of `songs` from the storage, removing unreferenced blobs
+    ///
+    /// Only the references themselves and the names of the blobs are read, listing all
+    /// keys would take long on object stores. Without any songs nothing is removed, the
+    /// state they were in may just be missing.
+    pub fn load(storage: Arc<dyn Storage>, songs: &[(String, SongId)]) -> io::Result<Self> {
+        let mut index = BlobIndex::default();
+        for (radio, song) in songs {
+            let hash = match storage.read_to_string(&format!("{radio}/{song}/{BLOB_REF}")) {
+                Ok(hash) => hash,
+                // Not transcoded yet
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+            *index.refs.entry(hash.clone()).or_default() += 1;
+            index
+                .songs
+                .entry(radio.clone())
+                .or_default()
+                .insert(*song, hash);
+        }
+        if songs.is_empty() {
+            return Ok(Self {
+                storage,
+                index: Arc::new(Mutex::new(index)),
+            });
+        }
+        let blobs = storage
+            .list_names(BLOBS_DIR)?
+            .into_iter()
+            .collect::<HashSet<_>>();
+        for hash in blobs {
+            if !index.refs.contains_key(&hash) {
+                eprintln!("Removing unreferenced blob {hash}");
+                storage.remove_prefix(&blob_key(&hash))?;
+            }
+        }
+        Ok(Self {
+            storage,
+            index: Arc::new(Mutex::new(index)),
+        })
+    }
+
+    /// Key prefix of the blob of a song, if it is transcoded
+    pub fn song_blob(&self, radio: &str, song: SongId) -> Option<String> {
+        let index = self.index.lock().unwrap();
+        let hash = index.songs.get(radio)?.get(&song)?;
+        (!index.pending.contains(hash)).then(|| blob_key(hash))
+    }
+
+    /// Length in seconds of a song, if it is transcoded
+    pub fn song_len(&self, radio: &str, song: SongId) -> Option<f64> {
+        let hash = {
+            let index = self.index.lock().unwrap();
+            let hash = index.songs.get(radio)?.get(&song)?;
+            if index.pending.contains(hash) {
+                return None;
+            }
+            if let Some(len) = index.lens.get(hash) {
+                return Some(*len);
+            }
+            hash.clone()
+        };
+        let len = self
+            .storage
+            .read_to_string(&format!("{}/len", blob_key(&hash)))
+            .ok()?
+            .parse()
+            .ok()?;
+        self.index.lock().unwrap().lens.insert(hash, len);
+        Some(len)
+    }
+
+    fn link_locked(
+        &self,
+        index: &mut BlobIndex,
+        radio: &str,
+        song: SongId,
+        hash: &str,
+    ) -> io::Result<()> {
+        self.storage
+            .write(&format!("{radio}/{song}/{BLOB_REF}"), hash.as_bytes())?;
+        index
+            .songs
+            .entry(radio.to_owned())
+            .or_default()
+            .insert(song, hash.to_owned());
+        Ok(())
+    }
+
+    /// Reference an existing blob from a song, `false` if there is no such blob yet
+    pub fn link_existing(&self, radio: &str, song: SongId, hash: &str) -> io::Result<bool> {
+        let mut index = self.index.lock().unwrap();
+        if !index.refs.contains_key(hash) {
+            return Ok(false);
+        }
+        self.link_locked(&mut index, radio, song, hash)?;
+        *index.refs.entry(hash.to_owned()).or_default() += 1;
+        Ok(true)
+    }
+
+    /// Store the segments, length and peaks of a song as a blob and reference it from the song
+    ///
+    /// If the same content was stored in the meantime, it is only referenced.
+    pub fn insert(
+        &self,
+        radio: &str,
+        song: SongId,
+        hash: &str,
+        segments: &[Vec<u8>],
+        len: f64,
+        peaks: &[u8],
+    ) -> io::Result<()> {
+        {
+            let mut index = self.index.lock().unwrap();
+            let refs = index.refs.entry(hash.to_owned()).or_default();
+            *refs += 1;
+            if *refs > 1 {
+                let linked = self.link_locked(&mut index, radio, song, hash);
+                if linked.is_err() {
+                    self.release(&mut index, hash)?;
+                }
+                return linked;
+            }
+            // The reference keeps the blob from being removed while it's written
+            index.pending.insert(hash.to_owned());
+        }
+        let key = blob_key(hash);
+        let written = segments
+            .iter()
+            .enumerate()
+            .try_for_each(|(i, segment)| self.storage.write(&format!("{key}/{i}.aac"), segment))
+            .and_then(|()| self.storage.write(&format!("{key}/peaks"), peaks))
+            .and_then(|()| {
+                self.storage
+                    .write(&format!("{key}/len"), len.to_string().as_bytes())
+            });
+        let mut index = self.index.lock().unwrap();
+        index.pending.remove(hash);
+        match written.and_then(|()| self.link_locked(&mut index, radio, song, hash)) {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                self.release(&mut index, hash)?;
+                Err(e)
+            }
+        }
+    }
+
+    fn release(&self, index: &mut BlobIndex, hash: &str) -> io::Result<()> {
+        let Some(refs) = index.refs.get_mut(hash) else {
+            return Ok(());
+        };
+        *refs -= 1;
+        if *refs == 0 {
+            index.refs.remove(hash);
+            index.lens.remove(hash);
+            self.storage.remove_prefix(&blob_key(hash))?;
+        }
+        Ok(())
+    }
+
+    /// Drop the reference of a song, removing its blob if it was the last one
+    pub fn unlink(&self, radio: &str, song: SongId) -> io::Result<()> {
+        let mut index = self.index.lock().unwrap();
+        let Some(hash) = index
+            .songs
+            .get_mut(radio)
+            .and_then(|songs| songs.remove(&song))
+        else {
+            return Ok(());
+        };
+        self.release(&mut index, &hash)
+    }
+
+    /// Drop the references of all songs of a radio
+    pub fn unlink_radio(&self, radio: &str) -> io::Result<()> {
+        let mut index = self.index.lock().unwrap();
+        let Some(songs) = index.songs.remove(radio) else {
+            return Ok(());
+        };
+        for hash in songs.into_values() {
+            self.release(&mut index, &hash)?;
+        }
+        Ok(())
+    }
+}
+
+/// File in a song directory naming the blob its segments are being moved into
+const PENDING_REF: &str = "blob.pending";
+
+/// Move a file of a song into its blob, where it may be already from an earlier try
+/// or from another song with the same content
+fn move_into_blob(from: &Path, to: &Path) -> io::Result<()> {
+    if to.exists() {
+        std::fs::remove_file(from)
+    } else {
+        rename(from, to)
+    }
+}
+
+/// Move the segments of a song stored in its own directory into a blob
+///
+/// Such songs were stored before their decoded audio was hashed, so the hash is
+/// over their segments instead and won't match new uploads of the same song.
+/// This only concerns data directories from before the storage was pluggable.
+///
+/// The blob is noted in the song directory before anything is moved, so a move that
+/// was interrupted is finished on the next start. Songs whose transcoding was
+/// interrupted have no length and are left alone.
+pub fn migrate_song_dir(root: &Path, song_dir: &Path) -> io::Result<()> {
+    if song_dir.join(BLOB_REF).exists() {
+        return Ok(());
+    }
+    let mut segments = vec![];
+    for file in read_dir(song_dir)? {
+        let file = file?;
+        let name = file.file_name().to_string_lossy().into_owned();
+        if let Some(seg) = name
+            .strip_suffix(".aac")
+            .and_then(|seg| seg.parse::<usize>().ok())
+        {
+            segments.push((seg, file.path()));
+        }
+    }
+    segments.sort();
+    let pending = song_dir.join(PENDING_REF);
+    let hash = match std::fs::read_to_string(&pending) {
+        Ok(hash) => hash,
+        Err(_) => {
+            if segments.is_empty() {
+                return Ok(());
+            }
+            if !song_dir.join("len").exists() {
+                eprintln!(
+                    "Not migrating incompletely transcoded song {}",
+                    song_dir.display()
+                );
+                return Ok(());
+            }
+            let mut hasher = Sha256::new();
+            for (_, path) in &segments {
+                hasher.update(std::fs::read(path)?);
+            }
+            let hash = hex::encode(hasher.finalize());
+            // Renamed, so a note cut short isn't taken for a blob
+            let tmp = song_dir.join(format!("{PENDING_REF}.tmp"));
+            write(&tmp, &hash)?;
+            rename(&tmp, &pending)?;
+            hash
+        }
+    };
+    let blob = root.join(BLOBS_DIR).join(&hash);
+    create_dir_all(&blob)?;
+    for (seg, path) in &segments {
+        move_into_blob(path, &blob.join(format!("{seg}.aac")))?;
+    }
+    let len = song_dir.join("len");
+    if len.exists() {
+        move_into_blob(&len, &blob.join("len"))?;
+    }
+    rename(pending, song_dir.join(BLOB_REF))
+}
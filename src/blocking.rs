@@ -1,8 +1,9 @@
 use std::{
-    collections::HashMap,
-    fs::{create_dir, remove_dir_all},
-    io::Cursor,
+    collections::{HashMap, HashSet},
+    fs::{remove_file, File},
+    io::Seek,
     path::PathBuf,
+    sync::{Arc, Mutex, MutexGuard},
     time::Duration,
 };
 
@@ -20,36 +21,132 @@ use symphonia::core::{
 };
 use tokio::sync::watch;
 
-use crate::{BANDWIDTHS, NUM_BANDWIDTHS};
+use crate::{
+    blobs::{content_hash, Blobs},
+    metadata::{cover_from_id3, cover_from_revision, SongMeta},
+    peaks::Peaks,
+    storage::Storage,
+    SongId, BANDWIDTHS, NUM_BANDWIDTHS,
+};
+
+/// The song currently on air in a radio
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NowPlaying {
+    pub song: SongId,
+    /// Seconds of the song played at the start of the segment
+    pub elapsed: f64,
+    /// Seconds of the song left at the start of the segment
+    pub remaining: f64,
+    /// The song after this one in the order
+    pub next: Option<SongId>,
+}
+
+/// Published to the listeners of a radio every interval
+#[derive(Debug, Clone, Default)]
+pub struct Segment {
+    /// The segment as stored
+    pub data: Vec<u8>,
+    /// The segment recoded for each of the `BANDWIDTHS`
+    pub bands: [Vec<u8>; NUM_BANDWIDTHS],
+    /// `None` while playing silence or relaying
+    pub now: Option<NowPlaying>,
+    /// Number of the segment in the radio, counting from 1 at startup
+    pub seq: u64,
+}
 
 /// Messages, that can be sent to the blocking thread (mainly audio)
 #[derive(Debug, Clone)]
 pub enum ToBlocking {
-    /// Upload a song to segment and save (given a song id)
+    /// Upload a song to segment and save (given a song id), from a file removed afterwards
     Upload {
         radio: String,
-        song: u8,
+        song: SongId,
         ext: String,
-        data: Box<[u8]>,
+        upload: PathBuf,
     },
     /// Set a playlist order (order of song ids)
-    Order { radio: String, order: Vec<u8> },
+    Order { radio: String, order: Vec<SongId> },
     /// Remove a song
-    Remove { radio: String, song: u8 },
+    Remove { radio: String, song: SongId },
     /// Remove a radio
     RemoveRadio { radio: String },
     /// Add a radio
     AddRadio {
         radio: String,
-        stream: watch::Sender<(Vec<u8>, [Vec<u8>; NUM_BANDWIDTHS])>,
+        stream: watch::Sender<Segment>,
+    },
+}
+
+/// Messages, that the blocking thread sends back
+#[derive(Debug, Clone)]
+pub enum FromBlocking {
+    /// A song was transcoded, with the metadata read from it
+    Ingested {
+        radio: String,
+        song: SongId,
+        meta: SongMeta,
     },
 }
 
+/// An uploaded file, removed when dropped
+struct UploadFile(PathBuf);
+
+impl Drop for UploadFile {
+    fn drop(&mut self) {
+        if let Err(e) = remove_file(&self.0) {
+            eprintln!("Couldn't remove upload {}: {e}", self.0.display());
+        }
+    }
+}
+
+/// The songs being transcoded, by radio
+type TranscodingSongs = Arc<Mutex<HashSet<(String, SongId)>>>;
+
+/// A song listed as being transcoded until dropped, removing the song unlists it
+struct Transcoding {
+    songs: TranscodingSongs,
+    key: (String, SongId),
+}
+
+impl Transcoding {
+    fn new(songs: &TranscodingSongs, radio: &str, song: SongId) -> Self {
+        let key = (radio.to_owned(), song);
+        songs.lock().unwrap().insert(key.clone());
+        Self {
+            songs: songs.clone(),
+            key,
+        }
+    }
+
+    /// Locks the songs if this one wasn't removed, so it can't be removed while storing it
+    fn lock_if_listed(&self) -> Option<MutexGuard<'_, HashSet<(String, SongId)>>> {
+        let songs = self.songs.lock().unwrap();
+        songs.contains(&self.key).then_some(songs)
+    }
+}
+
+impl Drop for Transcoding {
+    fn drop(&mut self) {
+        self.songs.lock().unwrap().remove(&self.key);
+    }
+}
+
+/// A song being transcoded
+struct Ingest {
+    radio: String,
+    song: SongId,
+    meta: SongMeta,
+    blobs: Blobs,
+    to_async: tokio::sync::mpsc::UnboundedSender<FromBlocking>,
+    transcoding: Transcoding,
+    _upload: UploadFile,
+}
+
 fn decode_loop(
     mut format: Box<dyn FormatReader>,
     mut decoder: Box<dyn Decoder>,
     track_id: u32,
-    path: PathBuf,
+    mut ingest: Ingest,
 ) {
     use symphonia::core::conv::FromSample;
     use symphonia::core::errors::Error;
@@ -85,6 +182,9 @@ fn decode_loop(
             format.metadata().pop();
 
             // Consume the new metadata at the head of the metadata queue.
+            if let Some(revision) = format.metadata().current() {
+                ingest.meta.fill_from_revision(revision);
+            }
         }
 
         // If the packet does not belong to the selected track, skip over it.
@@ -148,8 +248,54 @@ fn decode_loop(
     }
     let rate = TARGET_RATE;
     let num_channels = 2;
-    let each_len = rate as usize * 10 * num_channels;
     let total_secs = pcm.len() as f64 / (rate as f64 * num_channels as f64);
+
+    let Ingest {
+        radio,
+        song,
+        mut meta,
+        blobs,
+        to_async,
+        transcoding,
+        _upload,
+    } = ingest;
+    drop(_upload);
+    // Only transcode audio that isn't stored yet, and drop songs removed in the meantime
+    let hash = content_hash(&pcm);
+    let Some(listed) = transcoding.lock_if_listed() else {
+        return;
+    };
+    let linked = blobs.link_existing(&radio, song, &hash);
+    drop(listed);
+    match linked {
+        Ok(true) => (),
+        Ok(false) => {
+            let segments = encode_segments(&pcm, rate, num_channels);
+            let peaks = Peaks::compute(&pcm, rate, num_channels).encode();
+            let Some(_listed) = transcoding.lock_if_listed() else {
+                return;
+            };
+            if let Err(e) = blobs.insert(&radio, song, &hash, &segments, total_secs, &peaks) {
+                eprintln!("Couldn't store song {song} in radio {radio}: {e}");
+                return;
+            }
+        }
+        Err(e) => {
+            eprintln!("Couldn't link song {song} in radio {radio}: {e}");
+            return;
+        }
+    }
+
+    meta.duration = Some(total_secs);
+    let Ok(()) = to_async.send(FromBlocking::Ingested { radio, song, meta }) else {
+        eprintln!("Couldn't send metadata of song {song} back");
+        return;
+    };
+}
+
+/// Encode interleaved samples into segments of 10s
+fn encode_segments(pcm: &[i16], rate: usize, num_channels: usize) -> Vec<Vec<u8>> {
+    let each_len = rate * 10 * num_channels;
     let encoder = fdk_aac::enc::Encoder::new(EncoderParams {
         bit_rate: fdk_aac::enc::BitRate::VbrVeryHigh,
         sample_rate: rate as u32,
@@ -166,7 +312,8 @@ fn decode_loop(
     let samples_per_chunk = 2 * encoder_info.frameLength as usize;
 
     let mut buf: [u8; 1536] = [0; 1536];
-    for (i, part) in pcm.chunks(each_len).enumerate() {
+    let mut segments = vec![];
+    for part in pcm.chunks(each_len) {
         let mut compressed = Vec::<u8>::new();
 
         for chunk in part.chunks(samples_per_chunk) {
@@ -177,43 +324,37 @@ fn decode_loop(
             compressed.extend_from_slice(&buf[..output_size]);
         }
 
-        // Save file
-        let path = path.clone().join(format!("{i}.aac"));
-        std::fs::write(path, compressed).unwrap();
+        segments.push(compressed);
     }
-    std::fs::write(path.join("len"), total_secs.to_string()).unwrap();
+    segments
 }
+
 /// The blocking thread, contains mainly audio processing
 pub fn main(
     mut srx: tokio::sync::mpsc::UnboundedReceiver<ToBlocking>,
+    to_async: tokio::sync::mpsc::UnboundedSender<FromBlocking>,
     interval: Duration,
-    radios: HashMap<String, (Vec<u8>, watch::Sender<(Vec<u8>, [Vec<u8>; NUM_BANDWIDTHS])>)>,
-    root_dir: PathBuf,
+    radios: HashMap<String, (Vec<SongId>, watch::Sender<Segment>)>,
+    songs: Vec<(String, SongId)>,
+    storage: Arc<dyn Storage>,
 ) {
     // PANICKING: Since 10 != 0 and x - x / 10000 == x * 0.9999 >= 0 for Duration x which by Typedefinition is >= 0, this should never panic
     // TODO(optimize): if the above proof is correct, we can unwrap_unchecked (unsafe)
     let short_interval = interval
         .checked_sub(interval.checked_div(2).unwrap())
         .unwrap();
+    let blobs = Blobs::load(storage.clone(), &songs).expect("Couldn't load song blobs!");
+    let transcoding = TranscodingSongs::default();
     let mut last = std::time::Instant::now();
     let _start = last.clone();
     let mut radios_new = HashMap::new();
     radios_new.extend(radios.into_iter().map(|(name, (order, stream))| {
-        use fdk_aac::enc::*;
         (
             name.clone(),
             (
                 order,
                 stream,
-                BANDWIDTHS.map(|band| {
-                    Encoder::new(EncoderParams {
-                        bit_rate: BitRate::Cbr(band as u32),
-                        sample_rate: 44100,
-                        transport: fdk_aac::enc::Transport::Adts,
-                        channels: ChannelMode::Stereo,
-                    })
-                    .unwrap()
-                }),
+                band_encoders(44100),
                 fdk_aac::dec::Decoder::new(fdk_aac::dec::Transport::Adts),
                 true,
             ),
@@ -230,12 +371,16 @@ pub fn main(
                         radio,
                         song,
                         mut ext,
-                        data,
+                        upload,
                     } => {
                         // TODO(blocking): enable returning errors to user
-                        let path = root_dir.join(&radio).join(song.to_string());
-                        let Ok(()) = create_dir(&path) else {
-                            eprintln!("Couldn't create dir for song {song} in radio {radio} with root {}!", root_dir.display());
+                        let upload = UploadFile(upload);
+                        if !radios.contains_key(&radio) {
+                            eprintln!("Tried to upload song {song} to non-existent radio {radio}!");
+                            break 'mesg_check;
+                        }
+                        let Ok(mut file) = File::open(&upload.0) else {
+                            eprintln!("Couldn't open upload of song {song} in radio {radio}!");
                             break 'mesg_check;
                         };
                         // get extension hint
@@ -243,15 +388,22 @@ pub fn main(
                         let mut hint = Hint::new();
                         hint.with_extension(&ext);
 
-                        let mss =
-                            MediaSourceStream::new(Box::new(Cursor::new(data)), Default::default());
+                        // Tags symphonia doesn't map are still read from ID3
+                        let mut meta = SongMeta::default();
+                        let id3_tag = id3::Tag::read_from2(&mut file).ok();
+                        let Ok(_) = file.rewind() else {
+                            eprintln!("Couldn't rewind upload of song {song} in radio {radio}!");
+                            break 'mesg_check;
+                        };
+
+                        let mss = MediaSourceStream::new(Box::new(file), Default::default());
 
                         // Use the default options for metadata and format readers.
                         let meta_opts: MetadataOptions = Default::default();
                         let fmt_opts: FormatOptions = Default::default();
 
                         // Probe the media source.
-                        let probed = match symphonia::default::get_probe()
+                        let mut probed = match symphonia::default::get_probe()
                             .format(&hint, mss, &fmt_opts, &meta_opts)
                         {
                             Ok(probed) => probed,
@@ -261,8 +413,34 @@ pub fn main(
                             }
                         };
 
+                        // Metadata found while probing comes before that of the container
+                        let mut cover = None;
+                        if let Some(revision) =
+                            probed.metadata.get().as_ref().and_then(|m| m.current())
+                        {
+                            meta.fill_from_revision(revision);
+                            cover = cover_from_revision(revision).map(<[u8]>::to_vec);
+                        }
+
                         // Get the instantiated format reader.
-                        let format = probed.format;
+                        let mut format = probed.format;
+                        if let Some(revision) = format.metadata().current() {
+                            meta.fill_from_revision(revision);
+                            cover =
+                                cover.or_else(|| cover_from_revision(revision).map(<[u8]>::to_vec));
+                        }
+                        if let Some(tag) = &id3_tag {
+                            meta.fill_from_id3(tag);
+                            cover = cover.or_else(|| cover_from_id3(tag).map(<[u8]>::to_vec));
+                        }
+                        if let Some(cover) = cover {
+                            if let Err(e) = storage.write(&format!("{radio}/{song}/cover"), &cover)
+                            {
+                                eprintln!(
+                                    "Couldn't save cover of song {song} in radio {radio}: {e}"
+                                );
+                            }
+                        }
 
                         // Find the first audio track with a known (decodeable) codec.
                         let Some(track) = format
@@ -290,7 +468,16 @@ pub fn main(
 
                         // Store the track identifier, it will be used to filter packets.
                         let track_id = track.id;
-                        std::thread::spawn(move || decode_loop(format, decoder, track_id, path));
+                        let ingest = Ingest {
+                            transcoding: Transcoding::new(&transcoding, &radio, song),
+                            radio,
+                            song,
+                            meta,
+                            blobs: blobs.clone(),
+                            to_async: to_async.clone(),
+                            _upload: upload,
+                        };
+                        std::thread::spawn(move || decode_loop(format, decoder, track_id, ingest));
                     }
                     ToBlocking::Order { radio, order } => {
                         let Some((order_lock, _, _, _, new_song)) = radios.get_mut(&radio) else {
@@ -306,49 +493,37 @@ pub fn main(
                             break 'mesg_check;
                         };
                         order_lock.retain(|e| e != &song);
-                        let Ok(()) = remove_dir_all(root_dir.join(&radio).join(song.to_string()))
-                        else {
-                            eprintln!("Couldn't remove dir for song {song} in radio {radio} with root {}!", root_dir.display());
+                        transcoding.lock().unwrap().remove(&(radio.clone(), song));
+                        if let Err(e) = blobs.unlink(&radio, song) {
+                            eprintln!("Couldn't release blob of song {song} in radio {radio}: {e}");
+                        }
+                        let Ok(()) = storage.remove_prefix(&format!("{radio}/{song}")) else {
+                            eprintln!("Couldn't remove song {song} in radio {radio} from storage!");
                             break 'mesg_check;
                         };
                     }
                     ToBlocking::RemoveRadio { radio } => {
                         radios.remove(&radio);
-                        let Ok(()) = remove_dir_all(root_dir.join(&radio)) else {
-                            eprintln!(
-                                "Couldn't remove dir for radio {radio} with root {}!",
-                                root_dir.display()
-                            );
+                        transcoding.lock().unwrap().retain(|(r, _)| r != &radio);
+                        if let Err(e) = blobs.unlink_radio(&radio) {
+                            eprintln!("Couldn't release blobs of radio {radio}: {e}");
+                        }
+                        let Ok(()) = storage.remove_prefix(&radio) else {
+                            eprintln!("Couldn't remove radio {radio} from storage!");
                             break 'mesg_check;
                         };
                     }
                     ToBlocking::AddRadio { radio, stream } => {
-                        use fdk_aac::enc::*;
                         radios.insert(
                             radio.clone(),
                             (
                                 vec![],
                                 stream,
-                                BANDWIDTHS.map(|band| {
-                                    Encoder::new(EncoderParams {
-                                        bit_rate: BitRate::Cbr(band as u32),
-                                        sample_rate: 44100,
-                                        transport: fdk_aac::enc::Transport::Adts,
-                                        channels: ChannelMode::Stereo,
-                                    })
-                                    .unwrap()
-                                }),
+                                band_encoders(44100),
                                 fdk_aac::dec::Decoder::new(fdk_aac::dec::Transport::Adts),
                                 true,
                             ),
                         );
-                        let Ok(()) = create_dir(root_dir.join(&radio)) else {
-                            eprintln!(
-                                "Couldn't create dir for radio {radio} with root {}!",
-                                root_dir.display()
-                            );
-                            break 'mesg_check;
-                        };
                     }
                 },
                 Err(tokio::sync::mpsc::error::TryRecvError::Empty) => recvd = false,
@@ -363,13 +538,12 @@ pub fn main(
             radios.iter_mut().par_bridge().for_each(
                 |(name, (order, stream, encoders, decoder, new_song))| {
                     let name = name.clone();
-                    let path = root_dir.join(&name);
-                    let lens: Box<[(u8, f64)]> = order
+                    let lens: Box<[(SongId, f64)]> = order
                         .iter()
                         .filter_map(|song| {
-                            std::fs::read_to_string(path.join(song.to_string()).join("len"))
-                                .ok()
-                                .and_then(|v| v.parse().map(|x| (*song, x)).ok())
+                            blobs
+                                .song_len(&name, *song)
+                                .map(|len| (*song, len))
                                 .ok_or(())
                                 .map_err(|_| {
                                     eprintln!("Couldn't get len for song {song} in radio {name}")
@@ -379,32 +553,42 @@ pub fn main(
                         .collect();
                     let total_len: f64 = lens.iter().map(|(_, len)| len).sum();
                     let time = time_s % total_len;
-                    let Some((song, offset, len)) = lens
+                    let Some((pos, (song, offset, len))) = lens
                         .iter()
                         .scan(0.0f64, |pre_len, (song, len)| {
                             *pre_len += len;
                             Some((song, *pre_len, len))
                         })
-                        .find(|(_, offset, _)| *offset >= time)
+                        .find_position(|(_, offset, _)| *offset >= time)
                     else {
                         let silence = include_bytes!("silence.aac");
-                        let Ok(_) = stream.send((
-                            silence.to_vec(),
-                            [(); NUM_BANDWIDTHS].map(|_| silence.to_vec()),
-                        )) else {
+                        let Ok(_) = stream.send(Segment {
+                            data: silence.to_vec(),
+                            bands: [(); NUM_BANDWIDTHS].map(|_| silence.to_vec()),
+                            now: None,
+                            seq: stream.borrow().seq + 1,
+                        }) else {
                             eprintln!("Couldn't send silence to radio {name}");
                             return;
                         };
                         return;
                     };
                     let time = time - (offset - len);
-                    let path = root_dir.join(&name).join(song.to_string());
+                    let now = NowPlaying {
+                        song: *song,
+                        elapsed: time,
+                        remaining: len - time,
+                        next: lens.get((pos + 1) % lens.len()).map(|(song, _)| *song),
+                    };
+                    let Some(path) = blobs.song_blob(&name, *song) else {
+                        eprintln!("Song {song} in radio {name} has no blob");
+                        return;
+                    };
                     let seg = (time / 10.0) as usize;
                     if seg == 0 {
                         *new_song = true;
                     }
-                    let Ok(data) = std::fs::read(path.join(seg.to_string()).with_extension("aac"))
-                    else {
+                    let Ok(data) = storage.read(&format!("{path}/{seg}.aac")) else {
                         eprintln!("Couldn't read song file {seg} of song {song} in radio {name}");
                         return;
                     };
@@ -416,7 +600,12 @@ pub fn main(
                         return;
                     };
                     *new_song = false;
-                    let Ok(()) = stream.send((data, segs)) else {
+                    let Ok(()) = stream.send(Segment {
+                        data,
+                        bands: segs,
+                        now: Some(now),
+                        seq: stream.borrow().seq + 1,
+                    }) else {
                         eprintln!("Couldn't send seg for radio {name}! Channel closed");
                         return;
                     };
@@ -446,7 +635,23 @@ impl From<fdk_aac::enc::EncoderError> for RecodeError {
     }
 }
 
-fn recode(
+/// An encoder for each of the `BANDWIDTHS`
+pub fn band_encoders(sample_rate: u32) -> [fdk_aac::enc::Encoder; NUM_BANDWIDTHS] {
+    use fdk_aac::enc::*;
+    BANDWIDTHS.map(|band| {
+        Encoder::new(EncoderParams {
+            bit_rate: BitRate::Cbr(band as u32),
+            sample_rate,
+            transport: fdk_aac::enc::Transport::Adts,
+            channels: ChannelMode::Stereo,
+        })
+        .unwrap()
+    })
+}
+
+/// Recode an ADTS segment for each of the `BANDWIDTHS`, `new_song` resets the codecs
+/// for a stream that doesn't continue the previous segment
+pub fn recode(
     data: Vec<u8>,
     encoders: &mut [fdk_aac::enc::Encoder; NUM_BANDWIDTHS],
     decoder: &mut fdk_aac::dec::Decoder,
@@ -468,15 +673,7 @@ fn recode(
     // eprintln!("setting up encoders");
     let sample_rate = stream_info.sampleRate as u32;
     if new_song {
-        *encoders = BANDWIDTHS.map(|band| {
-            Encoder::new(EncoderParams {
-                bit_rate: BitRate::Cbr(band as u32),
-                sample_rate,
-                transport: fdk_aac::enc::Transport::Adts,
-                channels: ChannelMode::Stereo,
-            })
-            .unwrap()
-        });
+        *encoders = band_encoders(sample_rate);
         for encoder in encoders.iter_mut() {
             let encoder_info = encoder.info().unwrap();
             let samples_per_chunk = 2 * encoder_info.frameLength as usize;
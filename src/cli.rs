@@ -15,10 +15,15 @@ enum Command {
     RemoveRadio { radio: String },
     RemoveUser { sub: String },
     AddUser { sub: String },
+    GrantRole { sub: String, role: String },
+    RevokeRole { sub: String },
     ListUsers,
+    Usage { sub: String },
     CountUsers,
     ListRadios,
     ListSongs { radio: String },
+    ListListeners,
+    ListSessions { radio: String },
     ReloadPages { path: PathBuf },
     PrintState,
     Save,
@@ -35,10 +40,15 @@ trait CliListener {
     fn remove_radio(&self, radio: String) -> Result<String>;
     fn remove_user(&self, sub: String) -> Result<String>;
     fn add_user(&self, sub: String) -> Result<String>;
+    fn grant_role(&self, sub: String, role: String) -> Result<String>;
+    fn revoke_role(&self, sub: String) -> Result<String>;
     fn list_users(&self) -> Result<Vec<String>>;
+    fn usage(&self, sub: String) -> Result<String>;
     fn count_users(&self) -> Result<usize>;
     fn list_radios(&self) -> Result<Vec<String>>;
     fn list_songs(&self, radio: String) -> Result<Vec<String>>;
+    fn list_listeners(&self) -> Result<Vec<String>>;
+    fn list_sessions(&self, radio: String) -> Result<Vec<String>>;
     fn reload_pages(&self, path: PathBuf) -> Result<String>;
     fn print_state(&self) -> Result<String>;
     fn save(&self) -> Result<String>;
@@ -55,11 +65,14 @@ fn main() {
         Command::RemoveRadio { radio } => client.remove_radio(radio),
         Command::RemoveUser { sub } => client.remove_user(sub),
         Command::AddUser { sub } => client.add_user(sub),
+        Command::GrantRole { sub, role } => client.grant_role(sub, role),
+        Command::RevokeRole { sub } => client.revoke_role(sub),
         Command::ListUsers => client.list_users().map(|x| {
             x.into_iter()
                 .reduce(|a, e| format!("{a}\n{e}"))
                 .unwrap_or(String::new())
         }),
+        Command::Usage { sub } => client.usage(sub),
         Command::CountUsers => client.count_users().map(|x| format!("{x}")),
         Command::ListRadios => client.list_radios().map(|x| {
             x.into_iter()
@@ -71,6 +84,16 @@ fn main() {
                 .reduce(|a, e| format!("{a}\n{e}"))
                 .unwrap_or(String::new())
         }),
+        Command::ListListeners => client.list_listeners().map(|x| {
+            x.into_iter()
+                .reduce(|a, e| format!("{a}\n{e}"))
+                .unwrap_or(String::new())
+        }),
+        Command::ListSessions { radio } => client.list_sessions(radio).map(|x| {
+            x.into_iter()
+                .reduce(|a, e| format!("{a}\n{e}"))
+                .unwrap_or(String::new())
+        }),
         Command::ReloadPages { path } => client.reload_pages(path),
         Command::PrintState => client.print_state(),
         Command::Save => client.save(),
@@ -17,6 +17,28 @@ pub enum PageError {
     UnsupportedFileType,
     #[display(fmt = "Authentication error")]
     AuthError,
+    #[display(fmt = "Invalid upload request")]
+    UploadError,
+    #[display(fmt = "Conflicts with the current state")]
+    Conflict,
+    #[display(fmt = "Invalid recording schedule")]
+    InvalidSchedule,
+    #[display(fmt = "Invalid relay URL")]
+    InvalidRelay,
+    #[display(fmt = "Invalid page of results")]
+    InvalidPage,
+    #[display(fmt = "Invalid tags")]
+    InvalidTags,
+    #[display(fmt = "Invalid share link")]
+    InvalidShare,
+    #[display(fmt = "Invalid role")]
+    InvalidRole,
+    #[display(fmt = "Listening back is only possible on the main stream")]
+    InvalidOffset,
+    #[display(fmt = "File is too large")]
+    TooLarge,
+    #[display(fmt = "Quota exceeded for {}", _0)]
+    QuotaExceeded(#[error(not(source))] &'static str),
 }
 
 impl ResponseError for PageError {
@@ -28,6 +50,17 @@ impl ResponseError for PageError {
             PageError::ResourceNotFound => StatusCode::BAD_REQUEST,
             PageError::UnsupportedFileType => StatusCode::BAD_REQUEST,
             PageError::AuthError => StatusCode::BAD_REQUEST,
+            PageError::UploadError => StatusCode::BAD_REQUEST,
+            PageError::Conflict => StatusCode::CONFLICT,
+            PageError::InvalidSchedule => StatusCode::BAD_REQUEST,
+            PageError::InvalidRelay => StatusCode::BAD_REQUEST,
+            PageError::InvalidPage => StatusCode::BAD_REQUEST,
+            PageError::InvalidTags => StatusCode::BAD_REQUEST,
+            PageError::InvalidShare => StatusCode::BAD_REQUEST,
+            PageError::InvalidRole => StatusCode::BAD_REQUEST,
+            PageError::InvalidOffset => StatusCode::BAD_REQUEST,
+            PageError::TooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            PageError::QuotaExceeded(_) => StatusCode::FORBIDDEN,
         }
     }
 }
@@ -0,0 +1,154 @@
+use serde::Serialize;
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::{broadcast, watch};
+
+use crate::{blocking::Segment, handlers::now_playing_info, AppState};
+
+/// How many past events are kept for clients resuming with `Last-Event-ID`
+const RESUME_WINDOW: usize = 128;
+
+/// Kinds of radio state changes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    NowPlaying,
+    Order,
+    Config,
+    SongAdded,
+    SongUpdated,
+    SongRenamed,
+    SongRemoved,
+    Listeners,
+}
+
+impl EventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::NowPlaying => "now-playing",
+            Self::Order => "order",
+            Self::Config => "config",
+            Self::SongAdded => "song-added",
+            Self::SongUpdated => "song-updated",
+            Self::SongRenamed => "song-renamed",
+            Self::SongRemoved => "song-removed",
+            Self::Listeners => "listeners",
+        }
+    }
+}
+
+/// A change of a radio, numbered per radio
+#[derive(Debug, Clone)]
+pub struct Event {
+    pub id: u64,
+    pub kind: EventKind,
+    /// JSON encoded payload
+    pub data: String,
+}
+
+impl Event {
+    /// Format as a Server-Sent Event
+    pub fn to_sse(&self) -> String {
+        format!(
+            "id: {}\nevent: {}\ndata: {}\n\n",
+            self.id,
+            self.kind.as_str(),
+            self.data
+        )
+    }
+
+    /// Format as a Server-Sent Event of a stream with several radios, the ids are per radio
+    /// so they're left out
+    pub fn to_radio_sse(&self, radio: &str) -> String {
+        let radio = serde_json::to_string(radio).unwrap_or_default();
+        format!(
+            "event: {}\ndata: {{\"radio\":{radio},\"data\":{}}}\n\n",
+            self.kind.as_str(),
+            self.data
+        )
+    }
+}
+
+#[derive(Debug)]
+struct EventsInner {
+    sender: broadcast::Sender<Event>,
+    /// Next event id and the resume window
+    log: Mutex<(u64, VecDeque<Event>)>,
+}
+
+/// Event feed of a radio (cheap to clone, shared between handlers)
+#[derive(Debug, Clone)]
+pub struct RadioEvents(Arc<EventsInner>);
+
+impl Default for RadioEvents {
+    fn default() -> Self {
+        Self(Arc::new(EventsInner {
+            sender: broadcast::channel(RESUME_WINDOW).0,
+            log: Mutex::new((1, VecDeque::new())),
+        }))
+    }
+}
+
+impl RadioEvents {
+    pub fn publish(&self, kind: EventKind, data: impl Serialize) {
+        let Ok(data) = serde_json::to_string(&data) else {
+            eprintln!("Couldn't serialize {} event", kind.as_str());
+            return;
+        };
+        // Sending while holding the log keeps subscribe from missing or duplicating events
+        let mut log = self.0.log.lock().unwrap();
+        let event = Event {
+            id: log.0,
+            kind,
+            data,
+        };
+        log.0 += 1;
+        if log.1.len() >= RESUME_WINDOW {
+            log.1.pop_front();
+        }
+        log.1.push_back(event.clone());
+        // No receivers is not an error
+        let _ = self.0.sender.send(event);
+    }
+
+    /// Get the events after `last_id` still in the resume window and a receiver for new ones
+    pub fn subscribe(&self, last_id: Option<u64>) -> (Vec<Event>, broadcast::Receiver<Event>) {
+        let log = self.0.log.lock().unwrap();
+        let backlog = match last_id {
+            Some(last_id) => log
+                .1
+                .iter()
+                .filter(|event| event.id > last_id)
+                .cloned()
+                .collect(),
+            None => vec![],
+        };
+        (backlog, self.0.sender.subscribe())
+    }
+}
+
+/// Publish now-playing changes of a radio, finishes when the radio is removed
+pub async fn now_playing_task(
+    state: Arc<AppState>,
+    radio: String,
+    mut stream: watch::Receiver<Segment>,
+    events: RadioEvents,
+) {
+    let mut last = None;
+    while stream.changed().await.is_ok() {
+        let song = stream.borrow_and_update().now.map(|now| now.song);
+        if song == last {
+            continue;
+        }
+        last = song;
+        let radio_states = state.radio_states.read().await;
+        let Some(radio_state) = radio_states.get(&radio) else {
+            return;
+        };
+        events.publish(
+            EventKind::NowPlaying,
+            now_playing_info(&*radio_state.read().await),
+        );
+    }
+}
@@ -1,119 +1,386 @@
-use crate::auth::{decode_token, Token};
-use crate::blocking::ToBlocking;
+use crate::auth::{cookie_sub, decode_token, GlobalRole, Token, TOKEN_COOKIE};
+use crate::blobs::song_blob_key;
+use crate::blocking::{Segment, ToBlocking};
 use crate::errors::PageError;
-use crate::{AppState, Config, PartialConfig, RadioState, SentConfig, BANDWIDTHS, NUM_BANDWIDTHS};
+use crate::events::{self, EventKind, RadioEvents};
+use crate::import::{self, ImportReport, ImportResult, ImportStatus, Limits, PendingSongs};
+use crate::listeners::unix_now;
+use crate::listeners::{ListenerCounts, Listeners};
+use crate::members::{Member, Role, MAX_MEMBERS};
+use crate::metadata::{image_type, MetaPatch, SongMeta};
+use crate::peaks::Peaks;
+use crate::quotas::{Usage, UsageReport};
+use crate::recordings::{self, ActiveRecording, Recorder, RecordingInfo, RecordingSchedule};
+use crate::relay::{self, Relay};
+use crate::search::Doc;
+use crate::sharing::{
+    self, Share, Visibility, DEFAULT_SHARE_LIFETIME, MAX_SHARES, MAX_SHARE_LIFETIME,
+};
+use crate::storage::Storage;
+use crate::templates::Value;
+use crate::timeshift::{self, Archive};
+use crate::uploads::{parse_metadata, PendingUpload, TUS_EXTENSIONS, TUS_VERSION};
+use crate::{
+    clean_tags, AppState, Config, NewRadio, PartialConfig, RadioState, SentConfig, SongId,
+    SongRecord, BANDWIDTHS, RESERVED_RADIO_IDS, ROUTE_RADIO_IDS,
+};
 use actix_multipart::Multipart;
 use actix_web::{
-    delete, put, routes,
+    delete,
+    http::StatusCode,
+    put, routes,
     web::{self},
-    HttpResponse, Responder,
+    HttpRequest, HttpResponse, HttpResponseBuilder, Responder,
 };
 use futures::StreamExt;
 use itertools::Itertools;
+use openidconnect::SubjectIdentifier;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, sync::Arc};
-use tokio::sync::{watch, RwLock};
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
+use tokio::{
+    io::AsyncWriteExt,
+    sync::{watch, RwLock},
+};
 
-#[routes]
-#[get("/")]
-#[get("/index.html")]
-pub async fn get_start_page(state: web::Data<Arc<AppState>>) -> impl Responder {
-    let mut page = state.pages.read().await[0].clone();
-    if let Some(start) = page.find("{radios}") {
-        if let Some(end) = page.find("{radios-end}") {
-            let snippet = (&page[start..end])
-                .to_owned()
-                .replace("{radios}", "")
-                .replace("{radios-end}", "");
-            let radio_states = state.radio_states.read().await;
-            let radios = radio_states
+/// What the page templates know about a radio
+fn radio_value(id: &str, radio_state: &RadioState) -> Value {
+    Value::map([
+        ("id", id.into()),
+        ("title", (&radio_state.config.title).into()),
+        ("description", (&radio_state.config.description).into()),
+        ("listeners", radio_state.listeners.counts().total.into()),
+        (
+            "tags",
+            radio_state
+                .config
+                .tags
                 .iter()
-                .map(|(id, data)| async {
-                    let RadioState {
-                        config: Config { title, description },
-                        ..
-                    } = &*data.read().await;
-                    snippet
-                        .replace("{id}", id)
-                        .replace("{title}", title)
-                        .replace("{description}", description)
-                })
-                .collect::<Vec<_>>();
-            let mut radio_text = String::new();
-            for radio in radios {
-                let radio = radio.await;
-                radio_text.push_str(&radio);
-            }
-            page.replace_range(start..end, &radio_text);
+                .map(|tag| tag.as_str().into())
+                .collect::<Vec<_>>()
+                .into(),
+        ),
+        ("visibility", radio_state.config.visibility.as_str().into()),
+    ])
+}
+
+/// Check that the user `sub` has at least `role` on a radio, as a member or by their global
+/// role (every permission check on radios goes through here)
+async fn authorize(
+    state: &AppState,
+    sub: &SubjectIdentifier,
+    radio_state: &RadioState,
+    role: Role,
+) -> Result<(), PageError> {
+    let global = state
+        .users
+        .read()
+        .await
+        .get(sub)
+        .map(|user| user.role)
+        .unwrap_or_default();
+    match radio_state.role(sub).max(global.radio_role()) {
+        Some(has) if has >= role => Ok(()),
+        _ => Err(PageError::AuthError),
+    }
+}
+
+/// Check that a radio can be seen and listened to, private radios need a share token or one
+/// of their members logged in and look like they don't exist otherwise
+async fn check_radio_access(
+    state: &AppState,
+    req: &HttpRequest,
+    radio_id: &str,
+    radio_state: &RadioState,
+    share: Option<&str>,
+) -> Result<(), PageError> {
+    let sub = cookie_sub(req, &state.oidc_client);
+    check_radio_access_of(state, sub.as_ref(), radio_id, radio_state, share).await
+}
+
+/// Like `check_radio_access`, for a user known from elsewhere than the cookie
+async fn check_radio_access_of(
+    state: &AppState,
+    sub: Option<&SubjectIdentifier>,
+    radio_id: &str,
+    radio_state: &RadioState,
+    share: Option<&str>,
+) -> Result<(), PageError> {
+    if radio_state.config.visibility != Visibility::Private
+        || share.is_some_and(|token| {
+            sharing::is_valid(token, radio_id, &radio_state.shares, &state.oidc_client)
+        })
+    {
+        return Ok(());
+    }
+    let sub = sub.ok_or(PageError::NotFound)?;
+    authorize(state, sub, radio_state, Role::Scheduler)
+        .await
+        .map_err(|_| PageError::NotFound)
+}
+
+/// Tags shown on the start page
+const POPULAR_TAGS: usize = 10;
+
+/// The start page with the radios `filter` accepts, the most used tags and the favorites
+/// of the user `sub`
+async fn render_start_page(
+    state: &AppState,
+    sub: Option<SubjectIdentifier>,
+    filter: impl Fn(&RadioState) -> bool,
+) -> String {
+    let radio_states = state.radio_states.read().await;
+    let followed = match &sub {
+        Some(sub) => state
+            .users
+            .read()
+            .await
+            .get(sub)
+            .map(|user| user.favorites.clone())
+            .unwrap_or_default(),
+        None => vec![],
+    };
+    let mut favorites = vec![];
+    for id in &followed {
+        let Some(radio_state) = radio_states.get(id) else {
+            continue;
+        };
+        let radio_state = radio_state.read().await;
+        let visible = match &sub {
+            _ if radio_state.config.visibility != Visibility::Private => true,
+            Some(sub) => authorize(state, sub, &radio_state, Role::Scheduler)
+                .await
+                .is_ok(),
+            None => false,
+        };
+        if visible {
+            favorites.push(radio_value(id, &radio_state));
         }
     }
-    HttpResponse::Ok().body(page.replace("{radios-end}", ""))
+    let mut radios = vec![];
+    let mut tag_counts = HashMap::<String, usize>::new();
+    for (id, radio_state) in radio_states.iter() {
+        let radio_state = radio_state.read().await;
+        // Only public radios are listed
+        if radio_state.config.visibility != Visibility::Public {
+            continue;
+        }
+        for tag in &radio_state.config.tags {
+            *tag_counts.entry(tag.clone()).or_default() += 1;
+        }
+        if filter(&radio_state) {
+            radios.push(radio_value(id, &radio_state));
+        }
+    }
+    let tags = tag_counts
+        .into_iter()
+        .sorted_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then_with(|| a.cmp(b)))
+        .take(POPULAR_TAGS)
+        .map(|(tag, count)| Value::map([("name", tag.into()), ("count", count.into())]))
+        .collect::<Vec<_>>();
+    state.pages.read().await[0].render(&Value::map([
+        ("empty", radios.is_empty().into()),
+        ("radios", radios.into()),
+        ("tags", tags.into()),
+        ("favorites", favorites.into()),
+    ]))
+}
+
+#[routes]
+#[get("/")]
+#[get("/index.html")]
+pub async fn get_start_page(req: HttpRequest, state: web::Data<Arc<AppState>>) -> impl Responder {
+    let sub = cookie_sub(&req, &state.oidc_client);
+    HttpResponse::Ok().body(render_start_page(&state, sub, |_| true).await)
+}
+
+#[routes]
+#[get("/tag/{tag}")]
+#[get("/tag/{tag}/")]
+pub async fn get_tag_page(
+    req: HttpRequest,
+    path: web::Path<String>,
+    state: web::Data<Arc<AppState>>,
+) -> impl Responder {
+    let tag = path.into_inner().to_lowercase();
+    HttpResponse::Ok().body(
+        render_start_page(
+            &state,
+            cookie_sub(&req, &state.oidc_client),
+            |radio_state| radio_state.config.tags.contains(&tag),
+        )
+        .await,
+    )
 }
 
-#[derive(Serialize, Deserialize)]
-struct SearchQuery {
+/// Encoding of search results
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchFormat {
+    #[default]
+    Html,
+    Json,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SearchQuery {
     q: String,
+    #[serde(default)]
+    format: SearchFormat,
+    /// Starting at 1
+    #[serde(default = "first_page")]
+    page: usize,
+    #[serde(default = "default_per_page")]
+    per_page: usize,
+    /// Only radios with this tag and their songs
+    tag: Option<String>,
+}
+
+fn first_page() -> usize {
+    1
+}
+
+fn default_per_page() -> usize {
+    20
+}
+
+/// Most results on one page
+const MAX_PER_PAGE: usize = 100;
+
+/// A radio or song found by a search
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum SearchHit {
+    Radio {
+        id: String,
+        #[serde(flatten)]
+        config: SentConfig,
+        score: f32,
+    },
+    Song {
+        radio: String,
+        #[serde(flatten)]
+        song: SongRecord,
+        score: f32,
+    },
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchResults {
+    query: String,
+    page: usize,
+    per_page: usize,
+    /// Number of hits on all pages
+    total: usize,
+    hits: Vec<SearchHit>,
 }
+
 #[routes]
 #[get("/search")]
 #[get("/search/")]
 pub async fn get_search_page(
     query: web::Query<SearchQuery>,
     state: web::Data<Arc<AppState>>,
-) -> impl Responder {
-    let query = query.into_inner().q;
-    let mut page = state.pages.read().await[0].clone();
-    if let Some(start) = page.find("{radios}") {
-        if let Some(end) = page.find("{radios-end}") {
-            let snippet = (&page[start..end])
-                .to_owned()
-                .replace("{radios}", "")
-                .replace("{radios-end}", "");
-            let radio_states = state.radio_states.read().await;
-            let radios = radio_states
-                .iter()
-                .map(|(id, data)| async {
-                    let RadioState {
-                        config: Config { title, description },
-                        ..
-                    } = &*data.read().await;
-                    if id.contains(&query) || title.contains(&query) || description.contains(&query)
-                    {
-                        Some(
-                            snippet
-                                .replace("{id}", id)
-                                .replace("{title}", title)
-                                .replace("{description}", description),
-                        )
-                    } else {
-                        None
-                    }
-                })
-                .collect::<Vec<_>>();
-            let mut radio_text = String::new();
-            for radio in radios {
-                if let Some(radio) = radio.await {
-                    radio_text.push_str(&radio);
-                }
+) -> Result<HttpResponse, PageError> {
+    let SearchQuery {
+        q,
+        format,
+        page,
+        per_page,
+        tag,
+    } = query.into_inner();
+    if page == 0 || per_page == 0 || per_page > MAX_PER_PAGE {
+        return Err(PageError::InvalidPage);
+    }
+    let results = state
+        .search
+        .search(&q, tag.map(|tag| tag.to_lowercase()).as_deref());
+    let total = results.len();
+    let radio_states = state.radio_states.read().await;
+    let mut hits = vec![];
+    let mut radios = vec![];
+    let mut songs = vec![];
+    for (doc, score) in results
+        .into_iter()
+        .skip((page - 1) * per_page)
+        .take(per_page)
+    {
+        // Skips what changed since the search, the index is updated after the radios
+        match doc {
+            Doc::Radio(id) => {
+                let Some(radio_state) = radio_states.get(&id) else {
+                    continue;
+                };
+                let radio_state = radio_state.read().await;
+                radios.push(radio_value(&id, &radio_state));
+                hits.push(SearchHit::Radio {
+                    config: SentConfig {
+                        title: radio_state.config.title.clone().into(),
+                        description: radio_state.config.description.clone().into(),
+                        tags: radio_state.config.tags.clone(),
+                        visibility: radio_state.config.visibility,
+                    },
+                    id,
+                    score,
+                });
+            }
+            Doc::Song(radio, song) => {
+                let Some(radio_state) = radio_states.get(&radio) else {
+                    continue;
+                };
+                let radio_state = radio_state.read().await;
+                let Some(record) = radio_state.song_record(song) else {
+                    continue;
+                };
+                songs.push(Value::map([
+                    ("radio", radio_value(&radio, &radio_state)),
+                    (
+                        "title",
+                        record.meta.title.as_deref().unwrap_or(&record.name).into(),
+                    ),
+                    (
+                        "artist",
+                        record.meta.artist.as_deref().unwrap_or_default().into(),
+                    ),
+                ]));
+                hits.push(SearchHit::Song {
+                    radio,
+                    song: record,
+                    score,
+                });
             }
-            page.replace_range(start..end, &radio_text);
         }
     }
-    HttpResponse::Ok().body(page.replace("{radios-end}", ""))
+    Ok(match format {
+        SearchFormat::Json => HttpResponse::Ok().json(SearchResults {
+            query: q,
+            page,
+            per_page,
+            total,
+            hits,
+        }),
+        SearchFormat::Html => {
+            HttpResponse::Ok().body(state.pages.read().await[0].render(&Value::map([
+                ("empty", hits.is_empty().into()),
+                ("radios", radios.into()),
+                ("songs", songs.into()),
+                ("query", q.into()),
+            ])))
+        }
+    })
 }
 
 #[routes]
 #[get("/auth")]
 #[get("/auth/")]
 pub async fn get_auth_page(state: web::Data<Arc<AppState>>) -> impl Responder {
-    HttpResponse::Ok().body(state.pages.read().await[3].clone())
+    HttpResponse::Ok().body(state.pages.read().await[3].render(&Value::map([])))
 }
 
 #[routes]
 #[get("/auth/settings")]
 #[get("/auth/settings/")]
 pub async fn get_settings_page(state: web::Data<Arc<AppState>>) -> impl Responder {
-    HttpResponse::Ok().body(state.pages.read().await[4].clone())
+    HttpResponse::Ok().body(state.pages.read().await[4].render(&Value::map([])))
 }
 
 #[routes]
@@ -121,28 +388,26 @@ pub async fn get_settings_page(state: web::Data<Arc<AppState>>) -> impl Responde
 #[get("/{radio}/")]
 #[get("/{radio}/index.html")]
 pub async fn get_radio_page(
+    req: HttpRequest,
     path: web::Path<String>,
+    query: web::Query<ShareQuery>,
     state: web::Data<Arc<AppState>>,
 ) -> Result<HttpResponse, PageError> {
     let id = path.into_inner();
-    // Extract Radio State
-    let Config { title, description } = state
-        .radio_states
-        .read()
-        .await
+    let share = query.into_inner().share;
+    let radio_states = state.radio_states.read().await;
+    let radio_state = radio_states
         .get(&id)
         .ok_or(PageError::NotFound)?
         .read()
-        .await
-        .config
-        .clone();
-    // Return formatted data
-    Ok(HttpResponse::Ok().body(
-        state.pages.read().await[1]
-            .replace("{title}", &title)
-            .replace("{id}", &id)
-            .replace("{description}", &description),
-    ))
+        .await;
+    check_radio_access(&state, &req, &id, &radio_state, share.as_deref()).await?;
+    let mut radio = radio_value(&id, &radio_state);
+    // The player passes the token on to the stream
+    if let (Value::Map(radio), Some(share)) = (&mut radio, share) {
+        radio.insert("share".to_owned(), share.into());
+    }
+    Ok(HttpResponse::Ok().body(state.pages.read().await[1].render(&radio)))
 }
 
 #[routes]
@@ -150,26 +415,21 @@ pub async fn get_radio_page(
 #[get("/{radio}/edit/")]
 #[get("/{radio}/edit/index.html")]
 pub async fn get_radio_edit_page(
+    req: HttpRequest,
     path: web::Path<String>,
+    query: web::Query<ShareQuery>,
     state: web::Data<Arc<AppState>>,
 ) -> Result<HttpResponse, PageError> {
     let id = path.into_inner();
-    let Config { title, description } = state
-        .radio_states
-        .read()
-        .await
+    let radio_states = state.radio_states.read().await;
+    let radio_state = radio_states
         .get(&id)
         .ok_or(PageError::NotFound)?
         .read()
-        .await
-        .config
-        .clone();
-    Ok(HttpResponse::Ok().body(
-        state.pages.read().await[2]
-            .replace("{title}", &title)
-            .replace("{id}", &id)
-            .replace("{description}", &description),
-    ))
+        .await;
+    check_radio_access(&state, &req, &id, &radio_state, query.share.as_deref()).await?;
+    let radio = radio_value(&id, &radio_state);
+    Ok(HttpResponse::Ok().body(state.pages.read().await[2].render(&radio)))
 }
 
 #[routes]
@@ -188,17 +448,30 @@ pub async fn set_radio_config(
 
     let sub = decode_token(&token.ok_or(PageError::AuthError)?, &state.oidc_client)
         .ok_or(PageError::AuthError)?;
-    // IMPORTANT: this is only safe if no user can have the sub ADMIN (given with google)
-    if sub != radio_state_locked.owner && sub.as_str() != "ADMIN" {
-        Err(PageError::AuthError)?
-    }
+    authorize(&state, &sub, &radio_state_locked, Role::Editor).await?;
 
+    if let Some(tags) = &partial_config.tags {
+        radio_state_locked.config.tags = clean_tags(tags).ok_or(PageError::InvalidTags)?;
+    }
+    if let Some(visibility) = partial_config.visibility {
+        radio_state_locked.config.visibility = visibility;
+    }
     if let Some(title) = &partial_config.title {
         radio_state_locked.config.title = title.into();
     }
     if let Some(description) = &partial_config.description {
         radio_state_locked.config.description = description.into();
     }
+    radio_state_locked.events.publish(
+        EventKind::Config,
+        SentConfig {
+            title: radio_state_locked.config.title.clone().into(),
+            description: radio_state_locked.config.description.clone().into(),
+            tags: radio_state_locked.config.tags.clone(),
+            visibility: radio_state_locked.config.visibility,
+        },
+    );
+    state.search.index_radio(&id, &radio_state_locked);
 
     Ok(HttpResponse::Ok().body(format!(
         "Edited {id} with title: {}",
@@ -209,73 +482,189 @@ pub async fn set_radio_config(
 #[put("/{radio}")]
 pub async fn add_radio(
     path: web::Path<String>,
-    web::Json(config): web::Json<SentConfig>,
+    web::Json(NewRadio { config, relay }): web::Json<NewRadio>,
     state: web::Data<Arc<AppState>>,
     Token(token): Token,
 ) -> Result<HttpResponse, PageError> {
     let id = path.into_inner();
     let mut radio_states = state.radio_states.write().await;
 
-    if radio_states.contains_key(&id) {
+    if radio_states.contains_key(&id)
+        || RESERVED_RADIO_IDS.contains(&id.as_str())
+        || ROUTE_RADIO_IDS.contains(&id.as_str())
+    {
         return Err(PageError::NotFound.into());
     }
+    if relay
+        .as_deref()
+        .is_some_and(|url| !relay::is_valid_url(url))
+    {
+        Err(PageError::InvalidRelay)?
+    }
+    let tags = clean_tags(&config.tags).ok_or(PageError::InvalidTags)?;
 
     let sub = decode_token(&token.ok_or(PageError::AuthError)?, &state.oidc_client)
         .ok_or(PageError::AuthError)?;
 
-    state
-        .users
-        .write()
-        .await
-        .get_mut(&sub)
-        .ok_or(PageError::NotFound)?
-        .push(id.clone());
+    let mut users = state.users.write().await;
+    let user = users.get_mut(&sub).ok_or(PageError::NotFound)?;
+    // Relays make the server fetch any URL, so only trusted users may add them
+    if relay.is_some() && user.role < GlobalRole::Moderator {
+        Err(PageError::AuthError)?
+    }
+    state.quotas.check_radios(user.radios.len())?;
+    user.radios.push(id.clone());
+    drop(users);
 
-    let (tx, rx) = watch::channel((vec![], [(); NUM_BANDWIDTHS].map(|_| vec![])));
+    let (tx, rx) = watch::channel(Segment::default());
+    let events = RadioEvents::default();
+    actix_web::rt::spawn(events::now_playing_task(
+        (**state).clone(),
+        id.clone(),
+        rx.clone(),
+        events.clone(),
+    ));
+    let archive = Archive::new(state.timeshift_dir.join(&id), state.timeshift_window);
+    actix_web::rt::spawn(timeshift::archive_task(rx.clone(), archive.clone()));
+    let recorder = Recorder::new(None);
+    let listeners = Listeners::new(events.clone());
+    actix_web::rt::spawn(recordings::record_task(
+        rx.clone(),
+        recorder.clone(),
+        state.storage.clone(),
+        id.clone(),
+    ));
+    // Relay radios are fed by their relay, not scheduled by the blocking thread
+    let relay = match relay {
+        Some(url) => Some(Relay::start(url, tx, listeners.clone(), recorder.clone())),
+        None => {
+            state
+                .to_blocking
+                .send(ToBlocking::AddRadio {
+                    radio: id.clone(),
+                    stream: tx,
+                })
+                .map_err(PageError::from)?;
+            None
+        }
+    };
 
     let new_radio_state = RadioState {
         config: Config {
             title: config.title.into(),
             description: config.description.into(),
+            tags,
+            visibility: config.visibility,
         },
         stream: rx,
         song_map: HashMap::new(),
+        song_meta: HashMap::new(),
+        next_song_id: 0,
         song_order: Vec::new(),
         owner: sub,
+        members: HashMap::new(),
+        listeners,
+        events,
+        archive,
+        recorder,
+        relay,
+        shares: vec![],
     };
 
+    state.search.index_radio(&id, &new_radio_state);
     radio_states.insert(id.clone(), RwLock::new(new_radio_state));
-    state
-        .to_blocking
-        .send(ToBlocking::AddRadio {
-            radio: id.clone(),
-            stream: tx,
-        })
-        .map_err(PageError::from)?;
 
     Ok(HttpResponse::Created().body(format!("Radio added with ID: {}", id)))
 }
 
+fn user_agent(req: &HttpRequest) -> String {
+    req.headers()
+        .get(actix_web::http::header::USER_AGENT)
+        .and_then(|ua| ua.to_str().ok())
+        .unwrap_or("")
+        .to_owned()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListenQuery {
+    /// Seconds to go back in time, negative
+    offset: Option<i64>,
+    share: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ShareQuery {
+    /// Token of a share link, for private radios
+    share: Option<String>,
+}
+
 #[routes]
 #[get("/{radio}/listen")]
 #[get("/{radio}/listen/")]
 #[get("/{radio}/listen.aac")]
 pub async fn get_audio(
+    req: HttpRequest,
     path: web::Path<String>,
+    query: web::Query<ListenQuery>,
     state: web::Data<Arc<AppState>>,
 ) -> Result<HttpResponse, PageError> {
-    let stream = state
-        .radio_states
-        .read()
-        .await
-        .get(&path.into_inner())
-        .ok_or(PageError::NotFound)?
-        .read()
-        .await
-        .stream
-        .clone();
-    let stream = tokio_stream::wrappers::WatchStream::new(stream)
-        .map(|(buf, _)| Ok::<_, PageError>(actix_web::web::Bytes::copy_from_slice(&buf)));
+    let (stream, listeners, archive) = {
+        let radio_id = path.into_inner();
+        let radio_states = state.radio_states.read().await;
+        let radio_state = radio_states
+            .get(&radio_id)
+            .ok_or(PageError::NotFound)?
+            .read()
+            .await;
+        check_radio_access(
+            &state,
+            &req,
+            &radio_id,
+            &radio_state,
+            query.share.as_deref(),
+        )
+        .await?;
+        (
+            radio_state.stream.clone(),
+            radio_state.listeners.clone(),
+            radio_state.archive.clone(),
+        )
+    };
+    let next = match query.offset {
+        Some(offset) if offset < 0 => {
+            archive.first_since(unix_now().saturating_sub(offset.unsigned_abs()))
+        }
+        _ => None,
+    };
+    // Segments are read from the archive as long as it has the next one, so a client
+    // slower than the radio still gets all, then they are taken live
+    let segments = futures::stream::unfold(
+        (next, tokio_stream::wrappers::WatchStream::new(stream)),
+        move |(mut next, mut live)| {
+            let archive = archive.clone();
+            async move {
+                while let Some((seq, path)) = next.and_then(|next| archive.next(next)) {
+                    next = Some(seq + 1);
+                    // Segments may expire while others are sent
+                    if let Ok(data) = tokio::fs::read(path).await {
+                        return Some((data, (next, live)));
+                    }
+                }
+                loop {
+                    let segment = live.next().await?;
+                    if next.is_none_or(|next| segment.seq >= next) {
+                        return Some((segment.data, (None, live)));
+                    }
+                }
+            }
+        },
+    );
+    // Counts as a listener until the stream is dropped
+    let guard = listeners.connect(None, user_agent(&req));
+    let stream = segments.map(move |data| {
+        let _ = &guard;
+        Ok::<_, PageError>(actix_web::web::Bytes::from(data))
+    });
     Ok(HttpResponse::Ok()
         .keep_alive()
         .content_type("audio/aac")
@@ -287,28 +676,39 @@ pub async fn get_audio(
 #[get("/{radio}/listen/{band}/")]
 #[get("/{radio}/listen/{band}.aac")]
 pub async fn get_audio_band(
+    req: HttpRequest,
     path: web::Path<(String, usize)>,
+    query: web::Query<ListenQuery>,
     state: web::Data<Arc<AppState>>,
 ) -> Result<HttpResponse, PageError> {
     let (radio, band) = path.into_inner();
+    // Only the main stream is archived
+    if query.offset.is_some() {
+        Err(PageError::InvalidOffset)?
+    }
     let band_id = BANDWIDTHS
         .iter()
         .enumerate()
         .find(|(_, b)| b == &&band)
         .ok_or(PageError::NotFound)?
         .0;
-    let stream = state
-        .radio_states
-        .read()
-        .await
-        .get(&radio)
-        .ok_or(PageError::NotFound)?
-        .read()
-        .await
-        .stream
-        .clone();
-    let stream = tokio_stream::wrappers::WatchStream::new(stream).map(move |(_, bufs)| {
-        Ok::<_, PageError>(actix_web::web::Bytes::copy_from_slice(&bufs[band_id]))
+    let (stream, listeners) = {
+        let radio_states = state.radio_states.read().await;
+        let radio_state = radio_states
+            .get(&radio)
+            .ok_or(PageError::NotFound)?
+            .read()
+            .await;
+        check_radio_access(&state, &req, &radio, &radio_state, query.share.as_deref()).await?;
+        (radio_state.stream.clone(), radio_state.listeners.clone())
+    };
+    // Counts as a listener until the stream is dropped
+    let guard = listeners.connect(Some(band), user_agent(&req));
+    let stream = tokio_stream::wrappers::WatchStream::new(stream).map(move |mut segment| {
+        let _ = &guard;
+        Ok::<_, PageError>(actix_web::web::Bytes::from(std::mem::take(
+            &mut segment.bands[band_id],
+        )))
     });
     Ok(HttpResponse::Ok()
         .keep_alive()
@@ -317,82 +717,14 @@ pub async fn get_audio_band(
 }
 
 #[routes]
-#[put("/{radio}/songs/{song}")]
-#[put("/{radio}/songs/{song}/")]
-pub async fn upload_song(
-    path: web::Path<(String, String)>,
-    mut payload: Multipart,
-    state: web::Data<Arc<AppState>>,
-    Token(token): Token,
-) -> Result<HttpResponse, PageError> {
-    let (radio_id, song_id) = path.into_inner();
-    let mut song_data: Vec<u8> = Vec::new();
-    let radio_states = state.radio_states.read().await;
-    let mut radio_state = radio_states
-        .get(&radio_id)
-        .ok_or(PageError::NotFound)?
-        .write()
-        .await;
-
-    if radio_state.song_map.contains_key(&song_id) {
-        Err(PageError::NotFound)?
-    }
-
-    let sub = decode_token(&token.ok_or(PageError::AuthError)?, &state.oidc_client)
-        .ok_or(PageError::AuthError)?;
-    // IMPORTANT: this is only safe if no user can have the sub ADMIN (given with google)
-    if sub != radio_state.owner && sub.as_str() != "ADMIN" {
-        Err(PageError::AuthError)?
-    }
-
-    // Process each part in the multipart payload
-    while let Some(item) = payload.next().await {
-        let mut field = item.map_err(|_| PageError::MultipartError)?;
-
-        // Handle the content disposition to correctly find the file part
-        if let Some(content_disposition) = field.content_disposition() {
-            if content_disposition.get_name() == Some("file") {
-                // Read the file data part-by-part
-                while let Some(chunk) = field.next().await {
-                    let data = chunk.map_err(|_| PageError::MultipartError)?;
-                    song_data.extend_from_slice(&data);
-                }
-            }
-        }
-    }
-
-    let id = radio_state
-        .song_map
-        .values()
-        .sorted()
-        .fold(0, |a, e| if *e == a { e + 1 } else { a });
-
-    radio_state.song_map.insert(song_id.clone(), id);
-
-    state
-        .to_blocking
-        .send(ToBlocking::Upload {
-            radio: radio_id.clone(),
-            song: id,
-            ext: song_id[song_id.rfind('.').ok_or(PageError::UnsupportedFileType)?..].to_owned(),
-            data: song_data.into_boxed_slice(),
-        })
-        .map_err(PageError::from)?;
-
-    // Send a confirmation response
-    Ok(HttpResponse::Ok().body(format!(
-        "Song '{}' successfully uploaded to radio '{}'.",
-        song_id, radio_id
-    )))
-}
-
-#[routes]
-#[get("/{radio}/songs")]
-#[get("/{radio}/songs/")]
-pub async fn get_songs(
+#[get("/{radio}/listeners")]
+#[get("/{radio}/listeners/")]
+pub async fn get_listeners(
+    req: HttpRequest,
     path: web::Path<String>,
+    query: web::Query<ShareQuery>,
     state: web::Data<Arc<AppState>>,
-) -> Result<web::Json<Vec<String>>, PageError> {
+) -> Result<web::Json<ListenerCounts>, PageError> {
     let radio_id = path.into_inner();
     let radio_states = state.radio_states.read().await;
     let radio_state = radio_states
@@ -400,19 +732,36 @@ pub async fn get_songs(
         .ok_or(PageError::NotFound)?
         .read()
         .await;
+    check_radio_access(
+        &state,
+        &req,
+        &radio_id,
+        &radio_state,
+        query.share.as_deref(),
+    )
+    .await?;
 
-    Ok(web::Json(
-        radio_state.song_map.keys().cloned().collect_vec(),
-    ))
+    Ok(web::Json(radio_state.listeners.counts()))
+}
+
+/// The currently playing and next song of a radio
+#[derive(Debug, Clone, Serialize)]
+pub struct NowPlayingInfo {
+    song: Option<String>,
+    elapsed: f64,
+    remaining: f64,
+    next: Option<String>,
 }
 
 #[routes]
-#[get("/{radio}/order")]
-#[get("/{radio}/order/")]
-pub async fn get_song_order(
+#[get("/{radio}/now")]
+#[get("/{radio}/now/")]
+pub async fn get_now_playing(
+    req: HttpRequest,
     path: web::Path<String>,
+    query: web::Query<ShareQuery>,
     state: web::Data<Arc<AppState>>,
-) -> Result<web::Json<Vec<String>>, PageError> {
+) -> Result<web::Json<NowPlayingInfo>, PageError> {
     let radio_id = path.into_inner();
     let radio_states = state.radio_states.read().await;
     let radio_state = radio_states
@@ -420,52 +769,1729 @@ pub async fn get_song_order(
         .ok_or(PageError::NotFound)?
         .read()
         .await;
+    check_radio_access(
+        &state,
+        &req,
+        &radio_id,
+        &radio_state,
+        query.share.as_deref(),
+    )
+    .await?;
 
-    Ok(web::Json(radio_state.song_order.clone()))
+    Ok(web::Json(now_playing_info(&radio_state)))
+}
+
+/// Resolve the song ids the scheduler reports through `song_map`
+pub fn now_playing_info(radio_state: &RadioState) -> NowPlayingInfo {
+    let Some(now) = radio_state.stream.borrow().now else {
+        return NowPlayingInfo {
+            song: None,
+            elapsed: 0.0,
+            remaining: 0.0,
+            next: None,
+        };
+    };
+    let name = |id: SongId| {
+        radio_state
+            .song_map
+            .iter()
+            .find(|(_, &song)| song == id)
+            .map(|(name, _)| name.clone())
+    };
+    NowPlayingInfo {
+        song: name(now.song),
+        elapsed: now.elapsed,
+        remaining: now.remaining,
+        next: now.next.and_then(name),
+    }
 }
 
 #[routes]
-#[put("/{radio}/order")]
-#[put("/{radio}/order/")]
-pub async fn set_song_order(
+#[get("/{radio}/events")]
+#[get("/{radio}/events/")]
+pub async fn get_events(
+    req: HttpRequest,
     path: web::Path<String>,
-    payload: web::Json<Vec<String>>,
+    query: web::Query<ShareQuery>,
     state: web::Data<Arc<AppState>>,
-    Token(token): Token,
 ) -> Result<HttpResponse, PageError> {
-    let radio_id = path.into_inner();
-    let radio_states = state.radio_states.read().await;
-    let mut radio_state = radio_states
-        .get(&radio_id)
-        .ok_or(PageError::ResourceNotFound)?
-        .write()
-        .await;
-
-    let sub = decode_token(&token.ok_or(PageError::AuthError)?, &state.oidc_client)
-        .ok_or(PageError::AuthError)?;
-    // IMPORTANT: this is only safe if no user can have the sub ADMIN (given with google)
-    if sub != radio_state.owner && sub.as_str() != "ADMIN" {
-        Err(PageError::AuthError)?
-    }
+    let events = {
+        let radio_id = path.into_inner();
+        let radio_states = state.radio_states.read().await;
+        let radio_state = radio_states
+            .get(&radio_id)
+            .ok_or(PageError::NotFound)?
+            .read()
+            .await;
+        check_radio_access(
+            &state,
+            &req,
+            &radio_id,
+            &radio_state,
+            query.share.as_deref(),
+        )
+        .await?;
+        radio_state.events.clone()
+    };
+    let last_id = req
+        .headers()
+        .get("Last-Event-ID")
+        .and_then(|id| id.to_str().ok())
+        .and_then(|id| id.parse().ok());
+    let (backlog, rx) = events.subscribe(last_id);
+    let stream = futures::stream::iter(backlog)
+        .chain(
+            // Lagging clients skip the events they missed
+            tokio_stream::wrappers::BroadcastStream::new(rx)
+                .filter_map(|event| futures::future::ready(event.ok())),
+        )
+        .map(|event| Ok::<_, PageError>(actix_web::web::Bytes::from(event.to_sse())));
+    Ok(HttpResponse::Ok()
+        .keep_alive()
+        .content_type("text/event-stream")
+        .insert_header((actix_web::http::header::CACHE_CONTROL, "no-cache"))
+        // Keeps the compression middleware from buffering events
+        .insert_header((actix_web::http::header::CONTENT_ENCODING, "identity"))
+        .streaming(stream))
+}
 
-    radio_state.song_order = payload.into_inner();
+/// The extension of a song name, as a hint for the decoder
+fn song_ext(name: &str) -> Result<String, PageError> {
+    Ok(name[name.rfind('.').ok_or(PageError::UnsupportedFileType)?..].to_owned())
+}
 
-    state
-        .to_blocking
-        .send(ToBlocking::Order {
-            radio: radio_id.clone(),
-            order: radio_state
+/// Usage of the owner of a radio by their other radios, the caller adds the radio itself once
+/// locked (no radio may be locked while the others are read)
+async fn owner_usage(
+    state: &AppState,
+    radio_states: &HashMap<String, RwLock<RadioState>>,
+    radio_id: &str,
+) -> Result<Usage, PageError> {
+    let owner = radio_states
+        .get(radio_id)
+        .ok_or(PageError::NotFound)?
+        .read()
+        .await
+        .owner
+        .clone();
+    let radios = state
+        .users
+        .read()
+        .await
+        .get(&owner)
+        .map(|user| user.radios.clone())
+        .unwrap_or_default();
+    Ok(Usage::of(radio_states, &radios, Some(radio_id)).await)
+}
+
+/// Check that songs can be added to a radio, relay radios play their stream instead
+fn check_plays_songs(radio_state: &RadioState) -> Result<(), PageError> {
+    match radio_state.relay {
+        Some(_) => Err(PageError::Conflict),
+        None => Ok(()),
+    }
+}
+
+/// Add an uploaded song to a radio and hand its file to the blocking thread to ingest
+fn ingest_song(
+    state: &AppState,
+    radio_id: &str,
+    radio_state: &mut RadioState,
+    name: String,
+    ext: String,
+    upload: PathBuf,
+    size: u64,
+) -> Result<(), PageError> {
+    let id = radio_state
+        .allocate_song_id()
+        .ok_or(PageError::InternalError)?;
+
+    radio_state.song_map.insert(name.clone(), id);
+    radio_state.song_meta.insert(
+        id,
+        SongMeta {
+            size: Some(size),
+            ..Default::default()
+        },
+    );
+    radio_state.events.publish(EventKind::SongAdded, &name);
+    state.search.index_radio(radio_id, radio_state);
+
+    state
+        .to_blocking
+        .send(ToBlocking::Upload {
+            radio: radio_id.to_owned(),
+            song: id,
+            ext,
+            upload,
+        })
+        .map_err(PageError::from)
+}
+
+/// Check that the user `sub` can add the song `name` to a radio whose owner stores `usage`
+/// (this radio included), returning the bytes they can still upload
+async fn check_new_song(
+    state: &AppState,
+    sub: &SubjectIdentifier,
+    radio_id: &str,
+    radio_state: &RadioState,
+    name: &str,
+    usage: &Usage,
+) -> Result<Option<u64>, PageError> {
+    if radio_state.song_map.contains_key(name) {
+        Err(PageError::NotFound)?
+    }
+    authorize(state, sub, radio_state, Role::Editor).await?;
+    check_plays_songs(radio_state)?;
+    state.quotas.check_songs(usage, radio_id)?;
+    Ok(state.quotas.remaining_bytes(usage))
+}
+
+#[routes]
+#[put("/{radio}/songs/{song}")]
+#[put("/{radio}/songs/{song}/")]
+pub async fn upload_song(
+    path: web::Path<(String, String)>,
+    mut payload: Multipart,
+    state: web::Data<Arc<AppState>>,
+    Token(token): Token,
+) -> Result<HttpResponse, PageError> {
+    let (radio_id, song_id) = path.into_inner();
+    let sub = decode_token(&token.ok_or(PageError::AuthError)?, &state.oidc_client)
+        .ok_or(PageError::AuthError)?;
+    let ext = song_ext(&song_id)?;
+
+    // Nothing is locked while the song arrives, so it's checked again once stored
+    let remaining_bytes = {
+        let radio_states = state.radio_states.read().await;
+        let mut usage = owner_usage(&state, &radio_states, &radio_id).await?;
+        let radio_state = radio_states
+            .get(&radio_id)
+            .ok_or(PageError::NotFound)?
+            .read()
+            .await;
+        usage.add_radio(&radio_id, &radio_state);
+        check_new_song(&state, &sub, &radio_id, &radio_state, &song_id, &usage).await?
+    };
+
+    // The song is written to disk as it arrives, and removed again on errors
+    let (file, upload) = tempfile::NamedTempFile::new_in(&state.upload_dir)
+        .map_err(|_| PageError::InternalError)?
+        .into_parts();
+    let mut file = tokio::fs::File::from_std(file);
+    let mut size = 0u64;
+
+    // Process each part in the multipart payload
+    while let Some(item) = payload.next().await {
+        let mut field = item.map_err(|_| PageError::MultipartError)?;
+
+        // Handle the content disposition to correctly find the file part
+        if let Some(content_disposition) = field.content_disposition() {
+            if content_disposition.get_name() == Some("file") {
+                // Read the file data part-by-part
+                while let Some(chunk) = field.next().await {
+                    let data = chunk.map_err(|_| PageError::MultipartError)?;
+                    size += data.len() as u64;
+                    if size > state.max_upload_size {
+                        Err(PageError::TooLarge)?
+                    }
+                    if remaining_bytes.is_some_and(|max| size > max) {
+                        Err(PageError::QuotaExceeded("bytes"))?
+                    }
+                    file.write_all(&data)
+                        .await
+                        .map_err(|_| PageError::InternalError)?;
+                }
+            }
+        }
+    }
+
+    file.flush().await.map_err(|_| PageError::InternalError)?;
+    drop(file);
+
+    let radio_states = state.radio_states.read().await;
+    let mut usage = owner_usage(&state, &radio_states, &radio_id).await?;
+    let mut radio_state = radio_states
+        .get(&radio_id)
+        .ok_or(PageError::NotFound)?
+        .write()
+        .await;
+    usage.add_radio(&radio_id, &radio_state);
+    if check_new_song(&state, &sub, &radio_id, &radio_state, &song_id, &usage)
+        .await?
+        .is_some_and(|max| size > max)
+    {
+        Err(PageError::QuotaExceeded("bytes"))?
+    }
+
+    // The blocking thread removes the file once ingested
+    let upload = upload.keep().map_err(|_| PageError::InternalError)?;
+    ingest_song(
+        &state,
+        &radio_id,
+        &mut radio_state,
+        song_id.clone(),
+        ext,
+        upload,
+        size,
+    )?;
+
+    // Send a confirmation response
+    Ok(HttpResponse::Ok().body(format!(
+        "Song '{}' successfully uploaded to radio '{}'.",
+        song_id, radio_id
+    )))
+}
+
+/// A response speaking the tus protocol
+fn tus_response(status: StatusCode) -> HttpResponseBuilder {
+    let mut res = HttpResponse::build(status);
+    res.insert_header(("Tus-Resumable", TUS_VERSION));
+    res
+}
+
+/// The response to a tus request for another version of the protocol, if it is one
+fn tus_version_mismatch(req: &HttpRequest) -> Option<HttpResponse> {
+    (header(req, "Tus-Resumable") != Some(TUS_VERSION)).then(|| {
+        tus_response(StatusCode::PRECONDITION_FAILED)
+            .insert_header(("Tus-Version", TUS_VERSION))
+            .finish()
+    })
+}
+
+/// Check that the token belongs to a user with at least `role` on a radio
+/// The token of a request, from the `Authorization` header or else the cookie, which media
+/// elements send as they can't set the header
+fn request_token(req: &HttpRequest, token: Option<String>) -> Option<String> {
+    token.or_else(|| {
+        req.cookie(TOKEN_COOKIE)
+            .map(|cookie| cookie.value().to_owned())
+    })
+}
+
+async fn check_radio_role(
+    state: &AppState,
+    radio_id: &str,
+    token: Option<String>,
+    role: Role,
+) -> Result<(), PageError> {
+    let sub = decode_token(&token.ok_or(PageError::AuthError)?, &state.oidc_client)
+        .ok_or(PageError::AuthError)?;
+    let radio_states = state.radio_states.read().await;
+    let radio_state = radio_states
+        .get(radio_id)
+        .ok_or(PageError::NotFound)?
+        .read()
+        .await;
+    authorize(state, &sub, &radio_state, role).await
+}
+
+fn header<'a>(req: &'a HttpRequest, name: &str) -> Option<&'a str> {
+    req.headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+}
+
+#[routes]
+#[options("/{radio}/uploads")]
+#[options("/{radio}/uploads/")]
+pub async fn get_upload_options(state: web::Data<Arc<AppState>>) -> HttpResponse {
+    tus_response(StatusCode::NO_CONTENT)
+        .insert_header(("Tus-Version", TUS_VERSION))
+        .insert_header(("Tus-Extension", TUS_EXTENSIONS))
+        .insert_header(("Tus-Max-Size", state.max_upload_size.to_string()))
+        .finish()
+}
+
+#[routes]
+#[post("/{radio}/uploads")]
+#[post("/{radio}/uploads/")]
+pub async fn create_upload(
+    req: HttpRequest,
+    path: web::Path<String>,
+    state: web::Data<Arc<AppState>>,
+    Token(token): Token,
+) -> Result<HttpResponse, PageError> {
+    if let Some(res) = tus_version_mismatch(&req) {
+        return Ok(res);
+    }
+    let radio_id = path.into_inner();
+    let length: u64 = header(&req, "Upload-Length")
+        .and_then(|length| length.parse().ok())
+        .ok_or(PageError::UploadError)?;
+    let name = header(&req, "Upload-Metadata")
+        .map(parse_metadata)
+        .and_then(|mut metadata| metadata.remove("filename"))
+        .ok_or(PageError::UploadError)?;
+    song_ext(&name)?;
+    if length > state.max_upload_size {
+        Err(PageError::TooLarge)?
+    }
+
+    let sub = decode_token(&token.ok_or(PageError::AuthError)?, &state.oidc_client)
+        .ok_or(PageError::AuthError)?;
+    {
+        let radio_states = state.radio_states.read().await;
+        let mut usage = owner_usage(&state, &radio_states, &radio_id).await?;
+        let radio_state = radio_states
+            .get(&radio_id)
+            .ok_or(PageError::NotFound)?
+            .read()
+            .await;
+        usage.add_radio(&radio_id, &radio_state);
+        state.uploads.reserve(&mut usage);
+        if check_new_song(&state, &sub, &radio_id, &radio_state, &name, &usage)
+            .await?
+            .is_some_and(|max| length > max)
+        {
+            Err(PageError::QuotaExceeded("bytes"))?
+        }
+    }
+
+    state.uploads.expire();
+    let (_, file) = tempfile::NamedTempFile::new_in(&state.upload_dir)
+        .map_err(|_| PageError::InternalError)?
+        .into_parts();
+    let id = state.uploads.create(PendingUpload {
+        radio: radio_id.clone(),
+        name,
+        length,
+        offset: 0,
+        path: file.keep().map_err(|_| PageError::InternalError)?,
+        touched: unix_now(),
+    });
+
+    Ok(tus_response(StatusCode::CREATED)
+        .insert_header((
+            actix_web::http::header::LOCATION,
+            format!("/{radio_id}/uploads/{id}"),
+        ))
+        .finish())
+}
+
+#[routes]
+#[head("/{radio}/uploads/{upload}")]
+#[head("/{radio}/uploads/{upload}/")]
+pub async fn get_upload_offset(
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    state: web::Data<Arc<AppState>>,
+    Token(token): Token,
+) -> Result<HttpResponse, PageError> {
+    if let Some(res) = tus_version_mismatch(&req) {
+        return Ok(res);
+    }
+    let (radio_id, upload_id) = path.into_inner();
+    let upload = state.uploads.get(&upload_id).ok_or(PageError::NotFound)?;
+    let upload = upload.lock().await;
+    if upload.radio != radio_id {
+        Err(PageError::NotFound)?
+    }
+    check_radio_role(&state, &radio_id, token, Role::Editor).await?;
+
+    Ok(tus_response(StatusCode::OK)
+        .insert_header(("Upload-Offset", upload.offset.to_string()))
+        .insert_header(("Upload-Length", upload.length.to_string()))
+        .insert_header((actix_web::http::header::CACHE_CONTROL, "no-store"))
+        .finish())
+}
+
+#[routes]
+#[patch("/{radio}/uploads/{upload}")]
+#[patch("/{radio}/uploads/{upload}/")]
+pub async fn upload_chunk(
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    mut payload: web::Payload,
+    state: web::Data<Arc<AppState>>,
+    Token(token): Token,
+) -> Result<HttpResponse, PageError> {
+    if let Some(res) = tus_version_mismatch(&req) {
+        return Ok(res);
+    }
+    let (radio_id, upload_id) = path.into_inner();
+    if header(&req, "Content-Type") != Some("application/offset+octet-stream") {
+        Err(PageError::UnsupportedFileType)?
+    }
+    let offset: u64 = header(&req, "Upload-Offset")
+        .and_then(|offset| offset.parse().ok())
+        .ok_or(PageError::UploadError)?;
+
+    let upload_lock = state.uploads.get(&upload_id).ok_or(PageError::NotFound)?;
+    let mut upload = upload_lock.lock().await;
+    if upload.radio != radio_id {
+        Err(PageError::NotFound)?
+    }
+    check_radio_role(&state, &radio_id, token, Role::Editor).await?;
+    if upload.offset != offset {
+        Err(PageError::Conflict)?
+    }
+
+    let mut file = tokio::fs::OpenOptions::new()
+        .append(true)
+        .open(&upload.path)
+        .await
+        .map_err(|_| PageError::InternalError)?;
+    // Progress is kept chunk by chunk, so a dropped connection resumes from the last one
+    while let Some(chunk) = payload.next().await {
+        let Ok(data) = chunk else {
+            break;
+        };
+        if upload.offset + data.len() as u64 > upload.length {
+            Err(PageError::TooLarge)?
+        }
+        file.write_all(&data)
+            .await
+            .map_err(|_| PageError::InternalError)?;
+        upload.offset += data.len() as u64;
+        upload.touched = unix_now();
+    }
+    file.flush().await.map_err(|_| PageError::InternalError)?;
+    drop(file);
+
+    if upload.offset == upload.length {
+        state.uploads.remove(&upload_id);
+        let radio_states = state.radio_states.read().await;
+        let mut usage = owner_usage(&state, &radio_states, &radio_id).await?;
+        let mut radio_state = radio_states
+            .get(&radio_id)
+            .ok_or(PageError::NotFound)?
+            .write()
+            .await;
+        // Another upload may have taken the name or the quotas in the meantime
+        usage.add_radio(&radio_id, &radio_state);
+        state.uploads.reserve(&mut usage);
+        let checked = if radio_state.song_map.contains_key(&upload.name) {
+            Err(PageError::NotFound)
+        } else {
+            check_plays_songs(&radio_state)
+                .and_then(|()| state.quotas.check_songs(&usage, &radio_id))
+                .and_then(|()| match state.quotas.remaining_bytes(&usage) {
+                    Some(max) if upload.length > max => Err(PageError::QuotaExceeded("bytes")),
+                    _ => Ok(()),
+                })
+        };
+        if let Err(e) = checked {
+            let _ = tokio::fs::remove_file(&upload.path).await;
+            Err(e)?
+        }
+        ingest_song(
+            &state,
+            &radio_id,
+            &mut radio_state,
+            upload.name.clone(),
+            song_ext(&upload.name)?,
+            upload.path.clone(),
+            upload.length,
+        )?;
+    }
+
+    Ok(tus_response(StatusCode::NO_CONTENT)
+        .insert_header(("Upload-Offset", upload.offset.to_string()))
+        .finish())
+}
+
+#[routes]
+#[delete("/{radio}/uploads/{upload}")]
+#[delete("/{radio}/uploads/{upload}/")]
+pub async fn remove_upload(
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    state: web::Data<Arc<AppState>>,
+    Token(token): Token,
+) -> Result<HttpResponse, PageError> {
+    if let Some(res) = tus_version_mismatch(&req) {
+        return Ok(res);
+    }
+    let (radio_id, upload_id) = path.into_inner();
+    let upload = state.uploads.get(&upload_id).ok_or(PageError::NotFound)?;
+    let upload = upload.lock().await;
+    if upload.radio != radio_id {
+        Err(PageError::NotFound)?
+    }
+    check_radio_role(&state, &radio_id, token, Role::Editor).await?;
+
+    state.uploads.remove(&upload_id);
+    tokio::fs::remove_file(&upload.path)
+        .await
+        .map_err(|_| PageError::InternalError)?;
+    Ok(tus_response(StatusCode::NO_CONTENT).finish())
+}
+
+#[routes]
+#[post("/{radio}/import")]
+#[post("/{radio}/import/")]
+pub async fn import_songs(
+    path: web::Path<String>,
+    mut payload: web::Payload,
+    state: web::Data<Arc<AppState>>,
+    Token(token): Token,
+) -> Result<web::Json<ImportReport>, PageError> {
+    let radio_id = path.into_inner();
+    check_radio_role(&state, &radio_id, token, Role::Editor).await?;
+    check_plays_songs(
+        &*state
+            .radio_states
+            .read()
+            .await
+            .get(&radio_id)
+            .ok_or(PageError::NotFound)?
+            .read()
+            .await,
+    )?;
+
+    let (file, archive) = tempfile::NamedTempFile::new_in(&state.upload_dir)
+        .map_err(|_| PageError::InternalError)?
+        .into_parts();
+    let mut file = tokio::fs::File::from_std(file);
+    let mut size = 0u64;
+    while let Some(chunk) = payload.next().await {
+        let data = chunk.map_err(|_| PageError::UploadError)?;
+        size += data.len() as u64;
+        if size > state.max_import_size {
+            Err(PageError::TooLarge)?
+        }
+        file.write_all(&data)
+            .await
+            .map_err(|_| PageError::InternalError)?;
+    }
+    file.flush().await.map_err(|_| PageError::InternalError)?;
+    drop(file);
+
+    let limits = Limits {
+        max_file: state.max_upload_size,
+        max_total: state.max_import_size,
+    };
+    let upload_dir = state.upload_dir.clone();
+    let extracted =
+        tokio::task::spawn_blocking(move || import::extract(&archive, &upload_dir, limits))
+            .await
+            .map_err(|_| PageError::InternalError)?
+            .map_err(|_| PageError::UnsupportedFileType)?;
+    // Songs not ingested yet are removed on errors
+    let songs = PendingSongs::from(extracted.songs);
+
+    let radio_states = state.radio_states.read().await;
+    let mut usage = owner_usage(&state, &radio_states, &radio_id).await?;
+    let mut radio_state = radio_states
+        .get(&radio_id)
+        .ok_or(PageError::NotFound)?
+        .write()
+        .await;
+    usage.add_radio(&radio_id, &radio_state);
+    let mut report = ImportReport {
+        files: extracted.report,
+        order: None,
+    };
+    for song in songs {
+        let skipped = if radio_state.song_map.contains_key(&song.name) {
+            Some("name already taken".to_owned())
+        } else if let Err(e) = state.quotas.check_songs(&usage, &radio_id) {
+            Some(e.to_string())
+        } else if state
+            .quotas
+            .remaining_bytes(&usage)
+            .is_some_and(|max| song.size > max)
+        {
+            Some(PageError::QuotaExceeded("bytes").to_string())
+        } else {
+            song_ext(&song.name)
+                .and_then(|ext| {
+                    ingest_song(
+                        &state,
+                        &radio_id,
+                        &mut radio_state,
+                        song.name.clone(),
+                        ext,
+                        song.path.clone(),
+                        song.size,
+                    )
+                })
+                .err()
+                .map(|e| e.to_string())
+        };
+        match skipped {
+            Some(reason) => {
+                if let Err(e) = tokio::fs::remove_file(&song.path).await {
+                    eprintln!("Couldn't remove unpacked song {}: {e}", song.name);
+                }
+                report.files.push(ImportResult::skipped(song.name, reason));
+            }
+            None => {
+                *usage.songs.entry(radio_id.clone()).or_default() += 1;
+                usage.bytes += song.size;
+                report.files.push(ImportResult {
+                    name: song.name,
+                    status: ImportStatus::Queued,
+                });
+            }
+        }
+    }
+
+    // Songs of the playlist go last, in its order
+    if let Some(playlist) = extracted.playlist {
+        let mut order = radio_state
+            .song_order
+            .iter()
+            .filter(|name| !playlist.contains(name))
+            .cloned()
+            .collect_vec();
+        order.extend(
+            playlist
+                .into_iter()
+                .filter(|name| radio_state.song_map.contains_key(name))
+                .unique(),
+        );
+        radio_state.song_order = order;
+        radio_state
+            .events
+            .publish(EventKind::Order, &radio_state.song_order);
+        state.to_blocking.send(ToBlocking::Order {
+            radio: radio_id.clone(),
+            order: radio_state
                 .song_order
                 .iter()
-                .map(|name| radio_state.song_map.get(name).cloned())
-                .collect::<Option<Vec<u8>>>()
-                .ok_or(PageError::NotFound)?,
+                .filter_map(|name| radio_state.song_map.get(name).copied())
+                .collect(),
+        })?;
+        report.order = Some(radio_state.song_order.clone());
+    }
+
+    Ok(web::Json(report))
+}
+
+/// Changes to a song from the frontend
+#[derive(Debug, Deserialize)]
+pub struct SongPatch {
+    /// New name, must not be taken by another song
+    name: Option<String>,
+    #[serde(flatten)]
+    meta: MetaPatch,
+}
+
+#[routes]
+#[patch("/{radio}/songs/{song}")]
+#[patch("/{radio}/songs/{song}/")]
+pub async fn edit_song(
+    path: web::Path<(String, String)>,
+    web::Json(patch): web::Json<SongPatch>,
+    state: web::Data<Arc<AppState>>,
+    Token(token): Token,
+) -> Result<web::Json<SongRecord>, PageError> {
+    let (radio_id, song_name) = path.into_inner();
+    let radio_states = state.radio_states.read().await;
+    let mut radio_state = radio_states
+        .get(&radio_id)
+        .ok_or(PageError::NotFound)?
+        .write()
+        .await;
+
+    let sub = decode_token(&token.ok_or(PageError::AuthError)?, &state.oidc_client)
+        .ok_or(PageError::AuthError)?;
+    authorize(&state, &sub, &radio_state, Role::Editor).await?;
+
+    let id = *radio_state
+        .song_map
+        .get(&song_name)
+        .ok_or(PageError::NotFound)?;
+    // Check everything before changing anything
+    let new_name = patch.name.filter(|name| name != &song_name);
+    if let Some(name) = &new_name {
+        if name.is_empty() || name.contains('/') {
+            Err(PageError::UploadError)?
+        }
+        if radio_state.song_map.contains_key(name) {
+            Err(PageError::Conflict)?
+        }
+    }
+
+    // The id stays the same, so the order of the blocking thread is still right
+    if let Some(name) = new_name {
+        radio_state.song_map.remove(&song_name);
+        radio_state.song_map.insert(name.clone(), id);
+        let mut reordered = false;
+        for entry in radio_state.song_order.iter_mut() {
+            if *entry == song_name {
+                *entry = name.clone();
+                reordered = true;
+            }
+        }
+        radio_state.events.publish(
+            EventKind::SongRenamed,
+            HashMap::from([("from", &song_name), ("to", &name)]),
+        );
+        if reordered {
+            radio_state
+                .events
+                .publish(EventKind::Order, &radio_state.song_order);
+        }
+    }
+    radio_state
+        .song_meta
+        .entry(id)
+        .or_default()
+        .apply(patch.meta);
+
+    let record = radio_state
+        .song_record(id)
+        .ok_or(PageError::InternalError)?;
+    radio_state.events.publish(EventKind::SongUpdated, &record);
+    state.search.index_radio(&radio_id, &radio_state);
+    Ok(web::Json(record))
+}
+
+#[routes]
+#[get("/{radio}/songs")]
+#[get("/{radio}/songs/")]
+pub async fn get_songs(
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<ShareQuery>,
+    state: web::Data<Arc<AppState>>,
+) -> Result<web::Json<Vec<SongRecord>>, PageError> {
+    let radio_id = path.into_inner();
+    let radio_states = state.radio_states.read().await;
+    let radio_state = radio_states
+        .get(&radio_id)
+        .ok_or(PageError::NotFound)?
+        .read()
+        .await;
+    check_radio_access(
+        &state,
+        &req,
+        &radio_id,
+        &radio_state,
+        query.share.as_deref(),
+    )
+    .await?;
+
+    Ok(web::Json(
+        radio_state
+            .song_map
+            .values()
+            .filter_map(|&id| radio_state.song_record(id))
+            .sorted_by(|a, b| a.name.cmp(&b.name))
+            .collect_vec(),
+    ))
+}
+
+/// Serve the stored cover of a song
+async fn cover_response(
+    state: &AppState,
+    radio: &str,
+    song: SongId,
+    cache: &str,
+) -> Result<HttpResponse, PageError> {
+    let storage = state.storage.clone();
+    let key = format!("{radio}/{song}/cover");
+    let cover = tokio::task::spawn_blocking(move || storage.read(&key))
+        .await
+        .map_err(|_| PageError::InternalError)?
+        .map_err(|_| PageError::NotFound)?;
+    Ok(HttpResponse::Ok()
+        .content_type(image_type(&cover))
+        .insert_header((actix_web::http::header::CACHE_CONTROL, cache))
+        .body(cover))
+}
+
+#[routes]
+#[get("/{radio}/songs/{song}/cover")]
+#[get("/{radio}/songs/{song}/cover/")]
+pub async fn get_song_cover(
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    query: web::Query<ShareQuery>,
+    state: web::Data<Arc<AppState>>,
+) -> Result<HttpResponse, PageError> {
+    let (radio_id, song_name) = path.into_inner();
+    let song = {
+        let radio_states = state.radio_states.read().await;
+        let radio_state = radio_states
+            .get(&radio_id)
+            .ok_or(PageError::NotFound)?
+            .read()
+            .await;
+        check_radio_access(
+            &state,
+            &req,
+            &radio_id,
+            &radio_state,
+            query.share.as_deref(),
+        )
+        .await?;
+        *radio_state
+            .song_map
+            .get(&song_name)
+            .ok_or(PageError::NotFound)?
+    };
+
+    cover_response(&state, &radio_id, song, "max-age=3600").await
+}
+
+/// Encoding of served peaks
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PeaksFormat {
+    #[default]
+    Json,
+    /// As stored, see `Peaks::encode`
+    Binary,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PeaksQuery {
+    #[serde(default)]
+    format: PeaksFormat,
+    share: Option<String>,
+}
+
+#[routes]
+#[get("/{radio}/songs/{song}/peaks")]
+#[get("/{radio}/songs/{song}/peaks/")]
+pub async fn get_song_peaks(
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    query: web::Query<PeaksQuery>,
+    state: web::Data<Arc<AppState>>,
+) -> Result<HttpResponse, PageError> {
+    let (radio_id, song_name) = path.into_inner();
+    let song = {
+        let radio_states = state.radio_states.read().await;
+        let radio_state = radio_states
+            .get(&radio_id)
+            .ok_or(PageError::NotFound)?
+            .read()
+            .await;
+        check_radio_access(
+            &state,
+            &req,
+            &radio_id,
+            &radio_state,
+            query.share.as_deref(),
+        )
+        .await?;
+        *radio_state
+            .song_map
+            .get(&song_name)
+            .ok_or(PageError::NotFound)?
+    };
+
+    // Songs still transcoding and those stored before peaks existed have none
+    let storage = state.storage.clone();
+    let peaks = tokio::task::spawn_blocking(move || {
+        storage.read(&format!(
+            "{}/peaks",
+            song_blob_key(&*storage, &radio_id, song)?
+        ))
+    })
+    .await
+    .map_err(|_| PageError::InternalError)?
+    .map_err(|_| PageError::NotFound)?;
+
+    let mut res = HttpResponse::Ok();
+    res.insert_header((actix_web::http::header::CACHE_CONTROL, "max-age=3600"));
+    Ok(match query.format {
+        PeaksFormat::Binary => res.content_type("application/octet-stream").body(peaks),
+        PeaksFormat::Json => res.json(Peaks::decode(&peaks).map_err(|_| PageError::InternalError)?),
+    })
+}
+
+/// Keys and sizes of the segments `{prefix}/{n}.aac`, in order
+fn segment_sizes(storage: &dyn Storage, prefix: &str) -> std::io::Result<Vec<(String, u64)>> {
+    let mut segments = storage
+        .list(prefix)?
+        .into_iter()
+        .filter_map(|key| {
+            let seg = key
+                .strip_prefix(prefix)?
+                .strip_prefix('/')?
+                .strip_suffix(".aac")?
+                .parse::<u64>()
+                .ok()?;
+            Some((seg, key))
+        })
+        .collect_vec();
+    segments.sort();
+    segments
+        .into_iter()
+        .map(|(_, key)| {
+            let size = storage.size(&key)?;
+            Ok((key, size))
+        })
+        .collect()
+}
+
+/// Serve stored segments as a single AAC file, honouring a single `Range`
+/// (which is what players ask for)
+fn serve_segments(
+    req: &HttpRequest,
+    storage: Arc<dyn Storage>,
+    segments: Vec<(String, u64)>,
+) -> HttpResponse {
+    let total: u64 = segments.iter().map(|(_, size)| size).sum();
+    let range = match req
+        .headers()
+        .get(actix_web::http::header::RANGE)
+        .and_then(|range| range.to_str().ok())
+    {
+        Some(range) => match actix_files::HttpRange::parse(range, total) {
+            Ok(ranges) => ranges.first().copied(),
+            Err(_) => {
+                return HttpResponse::RangeNotSatisfiable()
+                    .insert_header((
+                        actix_web::http::header::CONTENT_RANGE,
+                        format!("bytes */{total}"),
+                    ))
+                    .finish()
+            }
+        },
+        None => None,
+    };
+    let (start, length) = range.map_or((0, total), |range| (range.start, range.length));
+
+    // Read only the segments overlapping the range, one at a time
+    let mut parts = vec![];
+    let mut offset = 0;
+    for (key, size) in segments {
+        let (from, to) = (
+            start.saturating_sub(offset).min(size),
+            (start + length).saturating_sub(offset).min(size),
+        );
+        if from < to {
+            parts.push((key, from as usize, to as usize));
+        }
+        offset += size;
+    }
+    let body = futures::stream::iter(parts).then(move |(key, from, to)| {
+        let storage = storage.clone();
+        async move {
+            let segment = tokio::task::spawn_blocking(move || storage.read(&key))
+                .await
+                .map_err(|_| PageError::InternalError)?
+                .map_err(|_| PageError::InternalError)?;
+            let to = to.min(segment.len());
+            Ok::<_, PageError>(web::Bytes::from(segment).slice(from.min(to)..to))
+        }
+    });
+
+    let mut res = match range {
+        Some(_) => {
+            let mut res = HttpResponse::PartialContent();
+            res.insert_header((
+                actix_web::http::header::CONTENT_RANGE,
+                format!("bytes {start}-{}/{total}", start + length - 1),
+            ));
+            res
+        }
+        None => HttpResponse::Ok(),
+    };
+    res.content_type("audio/aac")
+        .insert_header((actix_web::http::header::ACCEPT_RANGES, "bytes"))
+        .insert_header((actix_web::http::header::CACHE_CONTROL, "private, no-cache"))
+        // Keeps the compression middleware from changing the length
+        .insert_header((actix_web::http::header::CONTENT_ENCODING, "identity"))
+        .no_chunking(length)
+        .streaming(body)
+}
+
+#[routes]
+#[get("/{radio}/songs/{song}/preview")]
+#[get("/{radio}/songs/{song}/preview/")]
+pub async fn get_song_preview(
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    state: web::Data<Arc<AppState>>,
+    Token(token): Token,
+) -> Result<HttpResponse, PageError> {
+    let (radio_id, song_name) = path.into_inner();
+    check_radio_role(
+        &state,
+        &radio_id,
+        request_token(&req, token),
+        Role::Scheduler,
+    )
+    .await?;
+    let song = *state
+        .radio_states
+        .read()
+        .await
+        .get(&radio_id)
+        .ok_or(PageError::NotFound)?
+        .read()
+        .await
+        .song_map
+        .get(&song_name)
+        .ok_or(PageError::NotFound)?;
+
+    let storage = state.storage.clone();
+    let segments = tokio::task::spawn_blocking(move || {
+        segment_sizes(&*storage, &song_blob_key(&*storage, &radio_id, song)?)
+    })
+    .await
+    .map_err(|_| PageError::InternalError)?
+    .map_err(|_| PageError::NotFound)?;
+
+    Ok(serve_segments(&req, state.storage.clone(), segments))
+}
+
+/// Recordings of a radio, together with the running one and the schedule
+#[derive(Debug, Serialize)]
+pub struct RecordingsReport {
+    current: Option<ActiveRecording>,
+    schedule: Option<RecordingSchedule>,
+    recordings: Vec<RecordingInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StartRecording {
+    /// Seconds to record, until stopped if not given
+    duration: Option<u64>,
+}
+
+/// The recorder of a radio, checking that the token belongs to one of its editors
+async fn owned_recorder(
+    state: &AppState,
+    radio_id: &str,
+    token: Option<String>,
+) -> Result<Recorder, PageError> {
+    check_radio_role(state, radio_id, token, Role::Editor).await?;
+    Ok(state
+        .radio_states
+        .read()
+        .await
+        .get(radio_id)
+        .ok_or(PageError::NotFound)?
+        .read()
+        .await
+        .recorder
+        .clone())
+}
+
+#[routes]
+#[get("/{radio}/recordings")]
+#[get("/{radio}/recordings/")]
+pub async fn get_recordings(
+    path: web::Path<String>,
+    state: web::Data<Arc<AppState>>,
+    Token(token): Token,
+) -> Result<web::Json<RecordingsReport>, PageError> {
+    let radio_id = path.into_inner();
+    let recorder = owned_recorder(&state, &radio_id, token).await?;
+    let storage = state.storage.clone();
+    let recordings = tokio::task::spawn_blocking(move || recordings::list(&*storage, &radio_id))
+        .await
+        .map_err(|_| PageError::InternalError)?
+        .map_err(|_| PageError::InternalError)?;
+
+    Ok(web::Json(RecordingsReport {
+        current: recorder.current(),
+        schedule: recorder.schedule(),
+        recordings,
+    }))
+}
+
+#[routes]
+#[post("/{radio}/recordings")]
+#[post("/{radio}/recordings/")]
+pub async fn start_recording(
+    path: web::Path<String>,
+    web::Json(start): web::Json<StartRecording>,
+    state: web::Data<Arc<AppState>>,
+    Token(token): Token,
+) -> Result<HttpResponse, PageError> {
+    let radio_id = path.into_inner();
+    let recording = owned_recorder(&state, &radio_id, token)
+        .await?
+        .start(start.duration)
+        .ok_or(PageError::Conflict)?;
+
+    Ok(HttpResponse::Created().json(recording))
+}
+
+#[routes]
+#[post("/{radio}/recordings/stop")]
+#[post("/{radio}/recordings/stop/")]
+pub async fn stop_recording(
+    path: web::Path<String>,
+    state: web::Data<Arc<AppState>>,
+    Token(token): Token,
+) -> Result<web::Json<ActiveRecording>, PageError> {
+    let radio_id = path.into_inner();
+    let recording = owned_recorder(&state, &radio_id, token)
+        .await?
+        .stop()
+        .ok_or(PageError::NotFound)?;
+
+    Ok(web::Json(recording))
+}
+
+#[routes]
+#[put("/{radio}/recordings/schedule")]
+#[put("/{radio}/recordings/schedule/")]
+pub async fn set_recording_schedule(
+    path: web::Path<String>,
+    web::Json(schedule): web::Json<Option<RecordingSchedule>>,
+    state: web::Data<Arc<AppState>>,
+    Token(token): Token,
+) -> Result<HttpResponse, PageError> {
+    let radio_id = path.into_inner();
+    if schedule.is_some_and(|schedule| !schedule.is_valid()) {
+        return Err(PageError::InvalidSchedule);
+    }
+    owned_recorder(&state, &radio_id, token)
+        .await?
+        .set_schedule(schedule);
+
+    Ok(HttpResponse::Ok().body(format!(
+        "Updated recording schedule of radio with ID {radio_id}"
+    )))
+}
+
+#[routes]
+#[get("/{radio}/recordings/{recording}")]
+#[get("/{radio}/recordings/{recording}/")]
+pub async fn get_recording(
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    state: web::Data<Arc<AppState>>,
+    Token(token): Token,
+) -> Result<HttpResponse, PageError> {
+    let (radio_id, name) = path.into_inner();
+    check_radio_role(&state, &radio_id, request_token(&req, token), Role::Editor).await?;
+    // Names are start times, anything else could leave the recordings
+    let started = name.parse::<u64>().map_err(|_| PageError::NotFound)?;
+
+    let storage = state.storage.clone();
+    let prefix = format!("{}/{started}", recordings::recordings_key(&radio_id));
+    let segments = tokio::task::spawn_blocking(move || segment_sizes(&*storage, &prefix))
+        .await
+        .map_err(|_| PageError::InternalError)?
+        .map_err(|_| PageError::InternalError)?;
+    if segments.is_empty() {
+        return Err(PageError::NotFound);
+    }
+
+    let mut res = serve_segments(&req, state.storage.clone(), segments);
+    if res.status().is_success() {
+        res.headers_mut().insert(
+            actix_web::http::header::CONTENT_DISPOSITION,
+            actix_web::http::header::HeaderValue::from_str(&format!(
+                "attachment; filename=\"{radio_id}-{started}.aac\""
+            ))
+            .map_err(|_| PageError::InternalError)?,
+        );
+    }
+    Ok(res)
+}
+
+#[routes]
+#[delete("/{radio}/recordings/{recording}")]
+#[delete("/{radio}/recordings/{recording}/")]
+pub async fn remove_recording(
+    path: web::Path<(String, String)>,
+    state: web::Data<Arc<AppState>>,
+    Token(token): Token,
+) -> Result<HttpResponse, PageError> {
+    let (radio_id, name) = path.into_inner();
+    let recorder = owned_recorder(&state, &radio_id, token).await?;
+    let started = name.parse::<u64>().map_err(|_| PageError::NotFound)?;
+    if recorder
+        .current()
+        .is_some_and(|current| current.started == started)
+    {
+        return Err(PageError::Conflict);
+    }
+
+    let storage = state.storage.clone();
+    let prefix = format!("{}/{started}", recordings::recordings_key(&radio_id));
+    tokio::task::spawn_blocking(move || storage.remove_prefix(&prefix))
+        .await
+        .map_err(|_| PageError::InternalError)?
+        .map_err(|_| PageError::InternalError)?;
+
+    Ok(HttpResponse::Ok().body(format!(
+        "Removed recording {started} of radio with ID {radio_id}"
+    )))
+}
+
+#[routes]
+#[get("/{radio}/shares")]
+#[get("/{radio}/shares/")]
+pub async fn get_shares(
+    path: web::Path<String>,
+    state: web::Data<Arc<AppState>>,
+    Token(token): Token,
+) -> Result<web::Json<Vec<Share>>, PageError> {
+    let radio_id = path.into_inner();
+    check_radio_role(&state, &radio_id, token, Role::Owner).await?;
+    let radio_states = state.radio_states.read().await;
+    let radio_state = radio_states
+        .get(&radio_id)
+        .ok_or(PageError::NotFound)?
+        .read()
+        .await;
+    Ok(web::Json(
+        radio_state
+            .shares
+            .iter()
+            .filter(|share| !share.is_expired())
+            .cloned()
+            .collect(),
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewShare {
+    /// Seconds the link is valid, `DEFAULT_SHARE_LIFETIME` if not given
+    expires_in: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CreatedShare {
+    #[serde(flatten)]
+    share: Share,
+    /// Passed as `?share=` to the radio page and streams
+    token: String,
+}
+
+#[routes]
+#[post("/{radio}/shares")]
+#[post("/{radio}/shares/")]
+pub async fn create_share(
+    path: web::Path<String>,
+    web::Json(NewShare { expires_in }): web::Json<NewShare>,
+    state: web::Data<Arc<AppState>>,
+    Token(token): Token,
+) -> Result<HttpResponse, PageError> {
+    let radio_id = path.into_inner();
+    check_radio_role(&state, &radio_id, token, Role::Owner).await?;
+    let lifetime = expires_in.unwrap_or(DEFAULT_SHARE_LIFETIME);
+    if lifetime == 0 || lifetime > MAX_SHARE_LIFETIME {
+        Err(PageError::InvalidShare)?
+    }
+    let radio_states = state.radio_states.read().await;
+    let mut radio_state = radio_states
+        .get(&radio_id)
+        .ok_or(PageError::NotFound)?
+        .write()
+        .await;
+    radio_state.shares.retain(|share| !share.is_expired());
+    if radio_state.shares.len() >= MAX_SHARES {
+        Err(PageError::Conflict)?
+    }
+    let share = Share::new(lifetime);
+    let token = share
+        .token(&radio_id, &state.oidc_client)
+        .ok_or(PageError::InternalError)?;
+    radio_state.shares.push(share.clone());
+    Ok(HttpResponse::Created().json(CreatedShare { share, token }))
+}
+
+#[routes]
+#[delete("/{radio}/shares/{share}")]
+#[delete("/{radio}/shares/{share}/")]
+pub async fn revoke_share(
+    path: web::Path<(String, String)>,
+    state: web::Data<Arc<AppState>>,
+    Token(token): Token,
+) -> Result<HttpResponse, PageError> {
+    let (radio_id, share_id) = path.into_inner();
+    check_radio_role(&state, &radio_id, token, Role::Owner).await?;
+    let radio_states = state.radio_states.read().await;
+    let mut radio_state = radio_states
+        .get(&radio_id)
+        .ok_or(PageError::NotFound)?
+        .write()
+        .await;
+    let shares = radio_state.shares.len();
+    radio_state.shares.retain(|share| share.id != share_id);
+    if radio_state.shares.len() == shares {
+        Err(PageError::ResourceNotFound)?
+    }
+    Ok(HttpResponse::Ok().body(format!(
+        "Revoked share {share_id} of radio with ID {radio_id}"
+    )))
+}
+
+#[routes]
+#[get("/{radio}/members")]
+#[get("/{radio}/members/")]
+pub async fn get_members(
+    path: web::Path<String>,
+    state: web::Data<Arc<AppState>>,
+    Token(token): Token,
+) -> Result<web::Json<Vec<Member>>, PageError> {
+    let radio_id = path.into_inner();
+    check_radio_role(&state, &radio_id, token, Role::Scheduler).await?;
+    let radio_states = state.radio_states.read().await;
+    let radio_state = radio_states
+        .get(&radio_id)
+        .ok_or(PageError::NotFound)?
+        .read()
+        .await;
+    let owner = Member {
+        sub: radio_state.owner.clone(),
+        role: Role::Owner,
+    };
+    let members = radio_state
+        .members
+        .iter()
+        .map(|(sub, &role)| Member {
+            sub: sub.clone(),
+            role,
+        })
+        .sorted_by(|a, b| b.role.cmp(&a.role).then_with(|| a.sub.cmp(&b.sub)));
+    Ok(web::Json(std::iter::once(owner).chain(members).collect()))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetMember {
+    role: Role,
+}
+
+#[put("/{radio}/members/{sub}")]
+pub async fn set_member(
+    path: web::Path<(String, String)>,
+    web::Json(SetMember { role }): web::Json<SetMember>,
+    state: web::Data<Arc<AppState>>,
+    Token(token): Token,
+) -> Result<HttpResponse, PageError> {
+    let (radio_id, member) = path.into_inner();
+    let member = SubjectIdentifier::new(member);
+    check_radio_role(&state, &radio_id, token, Role::Owner).await?;
+    // Radios have a single owner, who the quotas are counted for
+    if role == Role::Owner {
+        Err(PageError::InvalidRole)?
+    }
+    let radio_states = state.radio_states.read().await;
+    let mut radio_state = radio_states
+        .get(&radio_id)
+        .ok_or(PageError::NotFound)?
+        .write()
+        .await;
+    if !state.users.read().await.contains_key(&member) {
+        Err(PageError::ResourceNotFound)?
+    }
+    if member == radio_state.owner {
+        Err(PageError::Conflict)?
+    }
+    if radio_state.members.len() >= MAX_MEMBERS && !radio_state.members.contains_key(&member) {
+        Err(PageError::Conflict)?
+    }
+    let res = match radio_state.members.insert(member.clone(), role) {
+        Some(_) => HttpResponse::Ok(),
+        None => HttpResponse::Created(),
+    }
+    .body(format!(
+        "{} is {role:?} of radio with ID {radio_id}",
+        member.as_str()
+    ));
+    Ok(res)
+}
+
+#[delete("/{radio}/members/{sub}")]
+pub async fn remove_member(
+    path: web::Path<(String, String)>,
+    state: web::Data<Arc<AppState>>,
+    Token(token): Token,
+) -> Result<HttpResponse, PageError> {
+    let (radio_id, member) = path.into_inner();
+    let member = SubjectIdentifier::new(member);
+    let sub = decode_token(&token.ok_or(PageError::AuthError)?, &state.oidc_client)
+        .ok_or(PageError::AuthError)?;
+    let radio_states = state.radio_states.read().await;
+    let mut radio_state = radio_states
+        .get(&radio_id)
+        .ok_or(PageError::NotFound)?
+        .write()
+        .await;
+    // Members can leave by themselves
+    if sub != member {
+        authorize(&state, &sub, &radio_state, Role::Owner).await?;
+    }
+    radio_state
+        .members
+        .remove(&member)
+        .ok_or(PageError::ResourceNotFound)?;
+    Ok(HttpResponse::Ok().body(format!(
+        "Removed {} from radio with ID {radio_id}",
+        member.as_str()
+    )))
+}
+
+#[routes]
+#[get("/{radio}/now/cover")]
+#[get("/{radio}/now/cover/")]
+pub async fn get_now_playing_cover(
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<ShareQuery>,
+    state: web::Data<Arc<AppState>>,
+) -> Result<HttpResponse, PageError> {
+    let radio_id = path.into_inner();
+    let now = {
+        let radio_states = state.radio_states.read().await;
+        let radio_state = radio_states
+            .get(&radio_id)
+            .ok_or(PageError::NotFound)?
+            .read()
+            .await;
+        check_radio_access(
+            &state,
+            &req,
+            &radio_id,
+            &radio_state,
+            query.share.as_deref(),
+        )
+        .await?;
+        let now = radio_state.stream.borrow().now;
+        now.ok_or(PageError::NotFound)?
+    };
+
+    // Follows the song on air, so must not be cached
+    cover_response(&state, &radio_id, now.song, "no-cache").await
+}
+
+#[routes]
+#[get("/{radio}/order")]
+#[get("/{radio}/order/")]
+pub async fn get_song_order(
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<ShareQuery>,
+    state: web::Data<Arc<AppState>>,
+) -> Result<web::Json<Vec<String>>, PageError> {
+    let radio_id = path.into_inner();
+    let radio_states = state.radio_states.read().await;
+    let radio_state = radio_states
+        .get(&radio_id)
+        .ok_or(PageError::NotFound)?
+        .read()
+        .await;
+    check_radio_access(
+        &state,
+        &req,
+        &radio_id,
+        &radio_state,
+        query.share.as_deref(),
+    )
+    .await?;
+
+    Ok(web::Json(radio_state.song_order.clone()))
+}
+
+#[routes]
+#[put("/{radio}/order")]
+#[put("/{radio}/order/")]
+pub async fn set_song_order(
+    path: web::Path<String>,
+    payload: web::Json<Vec<String>>,
+    state: web::Data<Arc<AppState>>,
+    Token(token): Token,
+) -> Result<HttpResponse, PageError> {
+    let radio_id = path.into_inner();
+    let radio_states = state.radio_states.read().await;
+    let mut radio_state = radio_states
+        .get(&radio_id)
+        .ok_or(PageError::ResourceNotFound)?
+        .write()
+        .await;
+
+    let sub = decode_token(&token.ok_or(PageError::AuthError)?, &state.oidc_client)
+        .ok_or(PageError::AuthError)?;
+    authorize(&state, &sub, &radio_state, Role::Scheduler).await?;
+
+    // Unknown songs reject the whole order before anything changes
+    let order = payload.into_inner();
+    let ids = order
+        .iter()
+        .map(|name| radio_state.song_map.get(name).cloned())
+        .collect::<Option<Vec<SongId>>>()
+        .ok_or(PageError::NotFound)?;
+
+    state
+        .to_blocking
+        .send(ToBlocking::Order {
+            radio: radio_id.clone(),
+            order: ids,
         })
-        .unwrap();
+        .map_err(PageError::from)?;
+    radio_state.song_order = order;
+    radio_state
+        .events
+        .publish(EventKind::Order, &radio_state.song_order);
 
     Ok(HttpResponse::Ok().body(format!("Update song order of radio with ID {}", radio_id)))
 }
 
+#[routes]
+#[get("/auth/usage")]
+#[get("/auth/usage/")]
+pub async fn get_usage(
+    state: web::Data<Arc<AppState>>,
+    Token(token): Token,
+) -> Result<web::Json<UsageReport>, PageError> {
+    let sub = decode_token(&token.ok_or(PageError::AuthError)?, &state.oidc_client)
+        .ok_or(PageError::AuthError)?;
+    let radio_states = state.radio_states.read().await;
+    let radios = state
+        .users
+        .read()
+        .await
+        .get(&sub)
+        .map(|user| user.radios.clone())
+        .ok_or(PageError::NotFound)?;
+
+    Ok(web::Json(UsageReport {
+        usage: Usage::of(&radio_states, &radios, None).await,
+        quotas: state.quotas,
+    }))
+}
+
+/// Unfollow a removed radio for all users and drop it from the radios of its owner
+pub async fn forget_radio(state: &AppState, radio: &str) {
+    for user in state.users.write().await.values_mut() {
+        user.radios.retain(|owned| owned != radio);
+        user.favorites.retain(|favorite| favorite != radio);
+    }
+}
+
+#[routes]
+#[get("/auth/favorites")]
+#[get("/auth/favorites/")]
+pub async fn get_favorites(
+    state: web::Data<Arc<AppState>>,
+    Token(token): Token,
+) -> Result<web::Json<Vec<String>>, PageError> {
+    let sub = decode_token(&token.ok_or(PageError::AuthError)?, &state.oidc_client)
+        .ok_or(PageError::AuthError)?;
+    let users = state.users.read().await;
+    let user = users.get(&sub).ok_or(PageError::NotFound)?;
+    Ok(web::Json(user.favorites.clone()))
+}
+
+#[put("/auth/favorites/{radio}")]
+pub async fn add_favorite(
+    path: web::Path<String>,
+    query: web::Query<ShareQuery>,
+    state: web::Data<Arc<AppState>>,
+    Token(token): Token,
+) -> Result<HttpResponse, PageError> {
+    let radio_id = path.into_inner();
+    let sub = decode_token(&token.ok_or(PageError::AuthError)?, &state.oidc_client)
+        .ok_or(PageError::AuthError)?;
+    // Holding the radios keeps the radio from being removed before it's followed
+    let radio_states = state.radio_states.read().await;
+    let radio_state = radio_states
+        .get(&radio_id)
+        .ok_or(PageError::NotFound)?
+        .read()
+        .await;
+    check_radio_access_of(
+        &state,
+        Some(&sub),
+        &radio_id,
+        &radio_state,
+        query.share.as_deref(),
+    )
+    .await?;
+    drop(radio_state);
+    let mut users = state.users.write().await;
+    let user = users.get_mut(&sub).ok_or(PageError::NotFound)?;
+    if user.favorites.contains(&radio_id) {
+        return Ok(HttpResponse::Ok().body(format!("Already following {radio_id}")));
+    }
+    user.favorites.push(radio_id.clone());
+    Ok(HttpResponse::Created().body(format!("Following {radio_id}")))
+}
+
+#[delete("/auth/favorites/{radio}")]
+pub async fn remove_favorite(
+    path: web::Path<String>,
+    state: web::Data<Arc<AppState>>,
+    Token(token): Token,
+) -> Result<HttpResponse, PageError> {
+    let radio_id = path.into_inner();
+    let sub = decode_token(&token.ok_or(PageError::AuthError)?, &state.oidc_client)
+        .ok_or(PageError::AuthError)?;
+    let mut users = state.users.write().await;
+    let user = users.get_mut(&sub).ok_or(PageError::NotFound)?;
+    let followed = user.favorites.len();
+    user.favorites.retain(|favorite| *favorite != radio_id);
+    if user.favorites.len() == followed {
+        Err(PageError::ResourceNotFound)?
+    }
+    Ok(HttpResponse::Ok().body(format!("Stopped following {radio_id}")))
+}
+
+/// Changes of all radios a user follows, as one event stream
+///
+/// Covers the radios followed when connecting, each event's data is
+/// `{"radio": id, "data": payload}` with the payload of the radio's own stream.
+#[routes]
+#[get("/auth/events")]
+#[get("/auth/events/")]
+pub async fn get_user_events(
+    req: HttpRequest,
+    state: web::Data<Arc<AppState>>,
+    Token(token): Token,
+) -> Result<HttpResponse, PageError> {
+    // `EventSource` can't send the header, but sends the cookie
+    let sub = match token {
+        Some(token) => decode_token(&token, &state.oidc_client),
+        None => cookie_sub(&req, &state.oidc_client),
+    }
+    .ok_or(PageError::AuthError)?;
+    let radio_states = state.radio_states.read().await;
+    let favorites = state
+        .users
+        .read()
+        .await
+        .get(&sub)
+        .map(|user| user.favorites.clone())
+        .ok_or(PageError::NotFound)?;
+    let mut streams = vec![];
+    for radio in favorites {
+        let Some(radio_state) = radio_states.get(&radio) else {
+            continue;
+        };
+        let radio_state = radio_state.read().await;
+        if radio_state.config.visibility == Visibility::Private
+            && authorize(&state, &sub, &radio_state, Role::Scheduler)
+                .await
+                .is_err()
+        {
+            continue;
+        }
+        let (_, rx) = radio_state.events.subscribe(None);
+        streams.push(
+            // Lagging clients skip the events they missed
+            tokio_stream::wrappers::BroadcastStream::new(rx)
+                .filter_map(|event| futures::future::ready(event.ok()))
+                .map(move |event| event.to_radio_sse(&radio)),
+        );
+    }
+    let stream = futures::stream::select_all(streams)
+        .map(|event| Ok::<_, PageError>(actix_web::web::Bytes::from(event)));
+    Ok(HttpResponse::Ok()
+        .keep_alive()
+        .content_type("text/event-stream")
+        .insert_header((actix_web::http::header::CACHE_CONTROL, "no-cache"))
+        // Keeps the compression middleware from buffering events
+        .insert_header((actix_web::http::header::CONTENT_ENCODING, "identity"))
+        .streaming(stream))
+}
+
+/// Remove a user from the radios they collaborate on
+pub async fn drop_memberships(
+    radio_states: &HashMap<String, RwLock<RadioState>>,
+    sub: &SubjectIdentifier,
+) {
+    for radio_state in radio_states.values() {
+        radio_state.write().await.members.remove(sub);
+    }
+}
+
 #[delete("/auth/user")]
 pub async fn remove_user(
     state: web::Data<Arc<AppState>>,
@@ -479,11 +2505,15 @@ pub async fn remove_user(
         .write()
         .await
         .remove(&sub)
-        .ok_or(PageError::NotFound)?;
+        .ok_or(PageError::NotFound)?
+        .radios;
 
     let mut radio_states = state.radio_states.write().await;
+    drop_memberships(&radio_states, &sub).await;
     for radio in radios {
         radio_states.remove(&radio);
+        state.search.remove_radio(&radio);
+        forget_radio(&state, &radio).await;
         let Ok(()) = state
             .to_blocking
             .send(ToBlocking::RemoveRadio {
@@ -510,20 +2540,21 @@ pub async fn remove_radio(
 
     let sub = decode_token(&token.ok_or(PageError::AuthError)?, &state.oidc_client)
         .ok_or(PageError::AuthError)?;
-    if sub
-        != radio_states
+    authorize(
+        &state,
+        &sub,
+        &*radio_states
             .get(&id)
             .ok_or(PageError::NotFound)?
             .read()
-            .await
-            .owner
-        // IMPORTANT: this is only safe if no user can have the sub ADMIN (given with google)
-        && sub.as_str() != "ADMIN"
-    {
-        Err(PageError::AuthError)?
-    }
+            .await,
+        Role::Owner,
+    )
+    .await?;
 
     radio_states.remove(&id);
+    state.search.remove_radio(&id);
+    forget_radio(&state, &id).await;
     state
         .to_blocking
         .send(ToBlocking::RemoveRadio { radio: id.clone() })
@@ -548,24 +2579,26 @@ pub async fn remove_song(
 
     let sub = decode_token(&token.ok_or(PageError::AuthError)?, &state.oidc_client)
         .ok_or(PageError::AuthError)?;
-    // IMPORTANT: this is only safe if no user can have the sub ADMIN (given with google)
-    if sub != radio_state.owner && sub.as_str() != "ADMIN" {
-        Err(PageError::AuthError)?
-    }
+    authorize(&state, &sub, &radio_state, Role::Editor).await?;
 
+    let song_id = *radio_state
+        .song_map
+        .get(&song_name)
+        .ok_or(PageError::NotFound)?;
     state
         .to_blocking
         .send(ToBlocking::Remove {
             radio: radio_id.clone(),
-            song: radio_state
-                .song_map
-                .get(&song_name)
-                .ok_or(PageError::NotFound)?
-                .clone(),
+            song: song_id,
         })
         .expect("Couldn't send to backend");
     radio_state.song_map.remove(&song_name);
+    radio_state.song_meta.remove(&song_id);
     radio_state.song_order.retain(|e| e != &song_name);
+    radio_state
+        .events
+        .publish(EventKind::SongRemoved, &song_name);
+    state.search.index_radio(&radio_id, &radio_state);
 
     Ok(HttpResponse::Ok().body(format!(
         "Remove song '{}' from radio with ID {}",
@@ -0,0 +1,491 @@
+use serde::Serialize;
+use std::{
+    collections::HashSet,
+    fs::{remove_file, File},
+    io::{self, BufReader, Read, Seek},
+    path::{Component, Path, PathBuf},
+};
+
+/// Entries an archive may contain at most
+const MAX_ENTRIES: usize = 4096;
+/// Size in bytes a playlist may have at most
+const MAX_PLAYLIST_SIZE: u64 = 1 << 20;
+/// Extensions of the audio files taken from an archive
+const AUDIO_EXTENSIONS: [&str; 10] = [
+    "mp3", "flac", "ogg", "wav", "m4a", "aac", "mp4", "aif", "aiff", "caf",
+];
+/// Extensions of the playlists giving the order
+const PLAYLIST_EXTENSIONS: [&str; 2] = ["m3u", "m3u8"];
+
+/// What happened to a file of an archive
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "kebab-case")]
+pub enum ImportStatus {
+    Queued,
+    Playlist,
+    Skipped { reason: String },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportResult {
+    pub name: String,
+    #[serde(flatten)]
+    pub status: ImportStatus,
+}
+
+impl ImportResult {
+    pub fn skipped(name: impl Into<String>, reason: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            status: ImportStatus::Skipped {
+                reason: reason.into(),
+            },
+        }
+    }
+}
+
+/// Result of an import, per file of the archive
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ImportReport {
+    pub files: Vec<ImportResult>,
+    /// The song order after the import, if the archive had a playlist
+    pub order: Option<Vec<String>>,
+}
+
+/// An audio file unpacked from an archive
+#[derive(Debug)]
+pub struct ExtractedSong {
+    pub name: String,
+    pub path: PathBuf,
+    pub size: u64,
+}
+
+/// Unpacked songs not handed on yet, the files of those left are removed when dropped
+#[derive(Debug)]
+pub struct PendingSongs(std::vec::IntoIter<ExtractedSong>);
+
+impl From<Vec<ExtractedSong>> for PendingSongs {
+    fn from(songs: Vec<ExtractedSong>) -> Self {
+        Self(songs.into_iter())
+    }
+}
+
+impl Iterator for PendingSongs {
+    type Item = ExtractedSong;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
+
+impl Drop for PendingSongs {
+    fn drop(&mut self) {
+        for song in self.0.by_ref() {
+            if let Err(e) = remove_file(&song.path) {
+                eprintln!("Couldn't remove unpacked song {}: {e}", song.name);
+            }
+        }
+    }
+}
+
+/// Bounds on unpacking, so archives can't fill the disk
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    /// Bytes of a single file
+    pub max_file: u64,
+    /// Bytes of all files together
+    pub max_total: u64,
+}
+
+/// The contents of an archive, each song in its own file
+#[derive(Debug, Default)]
+pub struct Extracted {
+    pub songs: Vec<ExtractedSong>,
+    /// Song names in the order of the first playlist
+    pub playlist: Option<Vec<String>>,
+    /// What happened to the files other than the songs
+    pub report: Vec<ImportResult>,
+}
+
+struct Extractor<'a> {
+    dir: &'a Path,
+    limits: Limits,
+    total: u64,
+    entries: usize,
+    names: HashSet<String>,
+    extracted: Extracted,
+}
+
+fn extension(name: &str) -> Option<String> {
+    Some(name.rsplit_once('.')?.1.to_ascii_lowercase())
+}
+
+/// Parse the song names of an M3U playlist, ignoring their directories
+fn parse_playlist(playlist: &str) -> Vec<String> {
+    playlist
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.rsplit(['/', '\\']).next())
+        .map(str::to_owned)
+        .collect()
+}
+
+impl Extractor<'_> {
+    /// Take an entry of an archive, `false` once no more entries should be read
+    fn add(&mut self, path: Option<&Path>, entry: impl Read) -> io::Result<bool> {
+        self.entries += 1;
+        if self.entries > MAX_ENTRIES {
+            self.extracted
+                .report
+                .push(ImportResult::skipped("", "too many files in archive"));
+            return Ok(false);
+        }
+        // Only the file names are used, but paths leaving the archive mark a malicious one
+        let Some(path) = path.filter(|path| {
+            path.components()
+                .all(|component| matches!(component, Component::Normal(_)))
+        }) else {
+            let name = path
+                .map(|path| path.display().to_string())
+                .unwrap_or_default();
+            self.extracted
+                .report
+                .push(ImportResult::skipped(name, "unsafe path"));
+            return Ok(true);
+        };
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            return Ok(true);
+        };
+        // Metadata of archivers like `__MACOSX/._song.mp3`
+        if name.starts_with('.') || path.starts_with("__MACOSX") {
+            return Ok(true);
+        }
+        let name = name.to_owned();
+        let ext = extension(&name).unwrap_or_default();
+
+        if PLAYLIST_EXTENSIONS.contains(&ext.as_str()) {
+            if self.extracted.playlist.is_some() {
+                self.extracted.report.push(ImportResult::skipped(
+                    name,
+                    "only the first playlist is used",
+                ));
+                return Ok(true);
+            }
+            let mut playlist = String::new();
+            entry
+                .take(MAX_PLAYLIST_SIZE)
+                .read_to_string(&mut playlist)?;
+            self.extracted.playlist = Some(parse_playlist(&playlist));
+            self.extracted.report.push(ImportResult {
+                name,
+                status: ImportStatus::Playlist,
+            });
+            return Ok(true);
+        }
+        if !AUDIO_EXTENSIONS.contains(&ext.as_str()) {
+            self.extracted
+                .report
+                .push(ImportResult::skipped(name, "not an audio file"));
+            return Ok(true);
+        }
+        if !self.names.insert(name.clone()) {
+            self.extracted
+                .report
+                .push(ImportResult::skipped(name, "duplicate name"));
+            return Ok(true);
+        }
+
+        // Declared sizes can lie, so only the bytes actually read count
+        let limit = self
+            .limits
+            .max_file
+            .min(self.limits.max_total.saturating_sub(self.total));
+        let (mut file, path) = tempfile::NamedTempFile::new_in(self.dir)?.into_parts();
+        let size = io::copy(&mut entry.take(limit + 1), &mut file)?;
+        if size > limit {
+            // Files too large are skipped, an archive too large ends the import
+            let file_too_large = limit == self.limits.max_file;
+            let reason = if file_too_large {
+                "file too large"
+            } else {
+                "archive too large"
+            };
+            self.extracted
+                .report
+                .push(ImportResult::skipped(name, reason));
+            return Ok(file_too_large);
+        }
+        self.total += size;
+        self.extracted.songs.push(ExtractedSong {
+            name,
+            path: path.keep()?,
+            size,
+        });
+        Ok(true)
+    }
+
+    fn zip(&mut self, file: File) -> io::Result<()> {
+        let mut archive = zip::ZipArchive::new(BufReader::new(file))?;
+        for i in 0..archive.len() {
+            let entry = archive.by_index(i)?;
+            if entry.is_dir() {
+                continue;
+            }
+            let path = entry.enclosed_name();
+            if !self.add(path.as_deref(), entry)? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn tar(&mut self, reader: impl Read) -> io::Result<()> {
+        let mut archive = tar::Archive::new(reader);
+        // Skipped entries are still unpacked (compressed tars), so they count as well
+        let mut scanned = 0u64;
+        for entry in archive.entries()? {
+            let entry = entry?;
+            scanned += entry.size();
+            if scanned > self.limits.max_total.saturating_mul(2) {
+                self.extracted
+                    .report
+                    .push(ImportResult::skipped("", "archive too large"));
+                break;
+            }
+            if entry.header().entry_type().is_dir() {
+                continue;
+            }
+            let path = entry.path()?.into_owned();
+            if !entry.header().entry_type().is_file() {
+                self.extracted.report.push(ImportResult::skipped(
+                    path.display().to_string(),
+                    "not a regular file",
+                ));
+                continue;
+            }
+            if !self.add(Some(&path), entry)? {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Unpack the songs and playlist of a zip, tar or gzipped tar archive into `dir`
+pub fn extract(archive: &Path, dir: &Path, limits: Limits) -> io::Result<Extracted> {
+    let mut file = File::open(archive)?;
+    let mut magic = [0; 4];
+    let read = file.read(&mut magic)?;
+    file.rewind()?;
+    let mut extractor = Extractor {
+        dir,
+        limits,
+        total: 0,
+        entries: 0,
+        names: HashSet::new(),
+        extracted: Extracted::default(),
+    };
+    let res = match &magic[..read] {
+        [b'P', b'K', 3, 4] => extractor.zip(file),
+        [0x1F, 0x8B, ..] => extractor.tar(flate2::read::GzDecoder::new(BufReader::new(file))),
+        _ => extractor.tar(BufReader::new(file)),
+    };
+    if let Err(e) = res {
+        for song in extractor.extracted.songs {
+            let _ = remove_file(song.path);
+        }
+        return Err(e);
+    }
+    Ok(extractor.extracted)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    const LIMITS: Limits = Limits {
+        max_file: 1 << 20,
+        max_total: 1 << 20,
+    };
+
+    fn zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(io::Cursor::new(vec![]));
+        for (name, data) in files {
+            writer
+                .start_file(*name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(data).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    /// Names are written as is, so they can leave the archive
+    fn tar(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(vec![]);
+        for (name, data) in files {
+            let mut header = tar::Header::new_gnu();
+            header.as_gnu_mut().unwrap().name[..name.len()].copy_from_slice(name.as_bytes());
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_entry_type(tar::EntryType::Regular);
+            header.set_cksum();
+            builder.append(&header, *data).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn extract_bytes(archive: &[u8], limits: Limits) -> Extracted {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("archive");
+        std::fs::write(&path, archive).unwrap();
+        let extracted = extract(&path, dir.path(), limits).unwrap();
+        for song in &extracted.songs {
+            assert!(song.path.starts_with(dir.path()));
+        }
+        extracted
+    }
+
+    fn song_names(extracted: &Extracted) -> Vec<&str> {
+        extracted
+            .songs
+            .iter()
+            .map(|song| song.name.as_str())
+            .collect()
+    }
+
+    fn skipped(extracted: &Extracted) -> Vec<(&str, &str)> {
+        extracted
+            .report
+            .iter()
+            .filter_map(|result| match &result.status {
+                ImportStatus::Skipped { reason } => Some((result.name.as_str(), reason.as_str())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn skips_zip_paths_leaving_the_archive() {
+        let archive = zip(&[
+            ("../evil.mp3", b"x"),
+            ("a/../../evil.mp3", b"x"),
+            ("album/song.mp3", b"song"),
+        ]);
+        let extracted = extract_bytes(&archive, LIMITS);
+        assert_eq!(song_names(&extracted), ["song.mp3"]);
+        assert_eq!(
+            skipped(&extracted),
+            [("", "unsafe path"), ("", "unsafe path")]
+        );
+    }
+
+    #[test]
+    fn skips_tar_paths_leaving_the_archive() {
+        let archive = tar(&[
+            ("../evil.mp3", b"x"),
+            ("/etc/evil.mp3", b"x"),
+            ("./song.mp3", b"song"),
+            ("album/other.mp3", b"other"),
+        ]);
+        let extracted = extract_bytes(&archive, LIMITS);
+        assert_eq!(song_names(&extracted), ["other.mp3"]);
+        assert_eq!(
+            skipped(&extracted),
+            [
+                ("../evil.mp3", "unsafe path"),
+                ("/etc/evil.mp3", "unsafe path"),
+                ("./song.mp3", "unsafe path"),
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_songs_and_playlist() {
+        let files: [(&str, &[u8]); 5] = [
+            ("b.mp3", b"b"),
+            ("list.m3u", b"#EXTM3U\n/music/a.flac\nb.mp3\n"),
+            ("a.flac", b"a"),
+            ("__MACOSX/._a.flac", b""),
+            ("cover.jpg", b""),
+        ];
+        for archive in [zip(&files), tar(&files), gzip(&tar(&files))] {
+            let extracted = extract_bytes(&archive, LIMITS);
+            assert_eq!(song_names(&extracted), ["b.mp3", "a.flac"]);
+            assert_eq!(
+                extracted.playlist,
+                Some(vec!["a.flac".to_owned(), "b.mp3".to_owned()])
+            );
+            assert_eq!(skipped(&extracted), [("cover.jpg", "not an audio file")]);
+        }
+    }
+
+    #[test]
+    fn limits_file_and_archive_size() {
+        let limits = Limits {
+            max_file: 10,
+            max_total: 25,
+        };
+        let files: [(&str, &[u8]); 6] = [
+            ("a.mp3", &[0; 5]),
+            ("b.mp3", &[0; 11]),
+            ("c.mp3", &[0; 10]),
+            ("d.mp3", &[0; 10]),
+            ("e.mp3", &[0; 1]),
+            ("f.mp3", &[0; 1]),
+        ];
+        for archive in [zip(&files), tar(&files)] {
+            let extracted = extract_bytes(&archive, limits);
+            assert_eq!(song_names(&extracted), ["a.mp3", "c.mp3", "d.mp3"]);
+            assert_eq!(
+                extracted.songs.iter().map(|song| song.size).sum::<u64>(),
+                25
+            );
+            assert_eq!(
+                skipped(&extracted),
+                [("b.mp3", "file too large"), ("e.mp3", "archive too large")]
+            );
+        }
+    }
+
+    #[test]
+    fn limits_entries() {
+        let names = (0..=MAX_ENTRIES)
+            .map(|i| format!("{i}.txt"))
+            .collect::<Vec<_>>();
+        let files = names
+            .iter()
+            .map(|name| (name.as_str(), &b""[..]))
+            .collect::<Vec<_>>();
+        let extracted = extract_bytes(&zip(&files), LIMITS);
+        let skipped = skipped(&extracted);
+        assert_eq!(skipped.len(), MAX_ENTRIES + 1);
+        assert_eq!(skipped.last(), Some(&("", "too many files in archive")));
+    }
+
+    #[test]
+    fn limits_scanned_tar_size() {
+        let limits = Limits {
+            max_file: 100,
+            max_total: 100,
+        };
+        let files: [(&str, &[u8]); 3] = [
+            ("a.txt", &[0; 150]),
+            ("b.txt", &[0; 60]),
+            ("c.mp3", &[0; 1]),
+        ];
+        let extracted = extract_bytes(&gzip(&tar(&files)), limits);
+        assert!(extracted.songs.is_empty());
+        assert_eq!(
+            skipped(&extracted),
+            [("a.txt", "not an audio file"), ("", "archive too large")]
+        );
+    }
+}
@@ -0,0 +1,159 @@
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    events::{EventKind, RadioEvents},
+    BANDWIDTHS,
+};
+
+/// How many finished sessions are kept per radio
+const HISTORY_LEN: usize = 256;
+
+/// A single connection to one of the audio streams
+#[derive(Debug, Clone, Serialize)]
+pub struct Session {
+    /// Bandwidth of the stream, `None` for the main stream
+    pub band: Option<usize>,
+    pub user_agent: String,
+    /// Unix timestamp of the connection
+    pub connected: u64,
+    /// Unix timestamp of the disconnect, `None` while still connected
+    pub disconnected: Option<u64>,
+}
+
+#[derive(Debug, Default)]
+struct ListenersInner {
+    next_id: u64,
+    active: HashMap<u64, Session>,
+    history: VecDeque<Session>,
+    /// Unix timestamp since which nobody listens, `None` while someone does
+    idle_since: Option<u64>,
+}
+
+/// Active and past listeners of a radio (cheap to clone, shared between handlers)
+#[derive(Debug, Clone)]
+pub struct Listeners {
+    inner: Arc<Mutex<ListenersInner>>,
+    events: RadioEvents,
+}
+
+/// Current listener counts of a radio
+#[derive(Debug, Clone, Serialize)]
+pub struct ListenerCounts {
+    pub total: usize,
+    pub main: usize,
+    pub bands: BTreeMap<usize, usize>,
+}
+
+/// Keeps a session active, records the disconnect when dropped
+#[derive(Debug)]
+pub struct ListenerGuard {
+    listeners: Listeners,
+    id: u64,
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+impl Listeners {
+    /// Track listeners, publishing count changes to `events`
+    pub fn new(events: RadioEvents) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(ListenersInner {
+                idle_since: Some(unix_now()),
+                ..Default::default()
+            })),
+            events,
+        }
+    }
+
+    /// Register a new connection, it stays active as long as the guard lives
+    pub fn connect(&self, band: Option<usize>, user_agent: String) -> ListenerGuard {
+        let id = {
+            let mut inner = self.inner.lock().unwrap();
+            let id = inner.next_id;
+            inner.next_id += 1;
+            inner.active.insert(
+                id,
+                Session {
+                    band,
+                    user_agent,
+                    connected: unix_now(),
+                    disconnected: None,
+                },
+            );
+            inner.idle_since = None;
+            id
+        };
+        self.events.publish(EventKind::Listeners, self.counts());
+        ListenerGuard {
+            listeners: self.clone(),
+            id,
+        }
+    }
+
+    fn disconnect(&self, id: u64) {
+        {
+            let mut inner = self.inner.lock().unwrap();
+            let Some(mut session) = inner.active.remove(&id) else {
+                return;
+            };
+            session.disconnected = Some(unix_now());
+            if inner.history.len() >= HISTORY_LEN {
+                inner.history.pop_front();
+            }
+            inner.history.push_back(session);
+            if inner.active.is_empty() {
+                inner.idle_since = Some(unix_now());
+            }
+        }
+        self.events.publish(EventKind::Listeners, self.counts());
+    }
+
+    pub fn counts(&self) -> ListenerCounts {
+        let inner = self.inner.lock().unwrap();
+        let mut bands: BTreeMap<usize, usize> = BANDWIDTHS.iter().map(|&b| (b, 0)).collect();
+        let mut main = 0;
+        for session in inner.active.values() {
+            match session.band {
+                Some(band) => *bands.entry(band).or_default() += 1,
+                None => main += 1,
+            }
+        }
+        ListenerCounts {
+            total: inner.active.len(),
+            main,
+            bands,
+        }
+    }
+
+    /// Unix timestamp since which nobody listens, `None` while someone does
+    pub fn idle_since(&self) -> Option<u64> {
+        self.inner.lock().unwrap().idle_since
+    }
+
+    /// Currently connected sessions followed by the most recent finished ones
+    pub fn sessions(&self) -> Vec<Session> {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .active
+            .values()
+            .chain(inner.history.iter().rev())
+            .cloned()
+            .collect()
+    }
+}
+
+impl Drop for ListenerGuard {
+    fn drop(&mut self) {
+        self.listeners.disconnect(self.id);
+    }
+}
@@ -5,7 +5,7 @@ use actix_web::{
     App, HttpServer, Result,
 };
 use ammonia::clean;
-use auth::{google_callback, google_redirect, OidcClient};
+use auth::{google_callback, google_redirect, GlobalRole, OidcClient};
 use clap::{Parser, Subcommand};
 use futures::{future::join_all, StreamExt, TryFutureExt};
 use itertools::Itertools;
@@ -21,8 +21,10 @@ use tokio::{
 };
 use zbus::interface;
 
+mod blobs;
+
 mod blocking;
-use blocking::ToBlocking;
+use blocking::{FromBlocking, Segment, ToBlocking};
 
 mod errors;
 
@@ -33,6 +35,51 @@ mod auth;
 
 mod cli;
 
+mod listeners;
+use listeners::Listeners;
+
+mod events;
+use events::{EventKind, RadioEvents};
+
+mod metadata;
+use metadata::SongMeta;
+
+mod quotas;
+use quotas::Quotas;
+
+mod uploads;
+use uploads::Uploads;
+
+mod import;
+
+mod peaks;
+
+mod timeshift;
+use timeshift::{Archive, TIMESHIFT_DIR};
+
+mod relay;
+use relay::Relay;
+
+mod recordings;
+use recordings::{Recorder, RecordingSchedule};
+
+mod templates;
+use templates::Template;
+
+mod search;
+use search::SearchIndex;
+
+mod members;
+use members::Role;
+
+mod sharing;
+use sharing::{Share, Visibility};
+
+mod state;
+
+mod storage;
+use storage::Storage;
+
 #[derive(Parser, Debug)]
 #[command(version, about)]
 #[command(propagate_version = true)]
@@ -45,6 +92,17 @@ struct Args {
     tls: Option<TlsArgs>,
     #[arg(short, long)]
     working_dir: Option<PathBuf>,
+    #[command(flatten)]
+    quotas: Quotas,
+    /// Maximum size in bytes of an uploaded song
+    #[arg(long, default_value_t = 1 << 30)]
+    max_upload_size: u64,
+    /// Maximum size in bytes of an imported archive and of the songs unpacked from it
+    #[arg(long, default_value_t = 1 << 33)]
+    max_import_size: u64,
+    /// Seconds of each radio kept for listening back in time, 0 to keep none
+    #[arg(long, default_value_t = 3 * 60 * 60)]
+    timeshift_window: u64,
 }
 
 #[derive(Subcommand, Debug, Clone)]
@@ -87,6 +145,45 @@ impl Deref for CleanString {
     }
 }
 
+/// Radio ids used by the data directory
+const RESERVED_RADIO_IDS: [&str; 5] = [
+    blobs::BLOBS_DIR,
+    UPLOADS_DIR,
+    TIMESHIFT_DIR,
+    "state",
+    "layout",
+];
+/// Radio ids whose pages would be shadowed by other routes
+const ROUTE_RADIO_IDS: [&str; 1] = ["tag"];
+
+/// Most tags a radio can have
+const MAX_TAGS: usize = 10;
+/// Most characters in a tag
+const MAX_TAG_LEN: usize = 32;
+
+/// Tags trimmed, lowercased and without duplicates, `None` if there are too many or
+/// one isn't made of letters, digits and dashes
+fn clean_tags(tags: &[String]) -> Option<Vec<String>> {
+    let mut cleaned: Vec<String> = vec![];
+    for tag in tags {
+        let tag = tag.trim().to_lowercase();
+        let len = tag.chars().count();
+        if len == 0 || len > MAX_TAG_LEN || !tag.chars().all(|c| c.is_alphanumeric() || c == '-') {
+            return None;
+        }
+        if !cleaned.contains(&tag) {
+            cleaned.push(tag);
+        }
+    }
+    (cleaned.len() <= MAX_TAGS).then_some(cleaned)
+}
+
+/// Directory of uploads waiting to be ingested, inside the data directory
+const UPLOADS_DIR: &str = "tmp";
+
+/// Identifies a song within a radio, never reused
+pub type SongId = u32;
+
 const NUM_BANDWIDTHS: usize = 4;
 
 const BANDWIDTHS: [usize; NUM_BANDWIDTHS] = [128000, 96000, 48000, 24000];
@@ -95,50 +192,139 @@ const BANDWIDTHS: [usize; NUM_BANDWIDTHS] = [128000, 96000, 48000, 24000];
 pub struct Config {
     title: CleanString,
     description: CleanString,
+    /// Genres and other keywords to browse by, see `clean_tags`
+    tags: Vec<String>,
+    visibility: Visibility,
 }
 /// Radio Config from frontend (not cleaned)
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct SentConfig {
     title: String,
     description: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    visibility: Visibility,
+}
+/// A radio to add from the frontend (not cleaned)
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewRadio {
+    #[serde(flatten)]
+    config: SentConfig,
+    /// URL of a stream to relay instead of playing uploaded songs
+    relay: Option<String>,
 }
 /// Partial Radio config from Frontend (not cleaned)
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct PartialConfig {
     title: Option<String>,
     description: Option<String>,
+    tags: Option<Vec<String>>,
+    visibility: Option<Visibility>,
 }
 /// Data for the radios
 #[derive(Debug, Clone)]
 pub struct RadioState {
     config: Config,
-    stream: watch::Receiver<(Vec<u8>, [Vec<u8>; NUM_BANDWIDTHS])>,
-    song_map: HashMap<String, u8>,
+    stream: watch::Receiver<Segment>,
+    song_map: HashMap<String, SongId>,
+    song_meta: HashMap<SongId, SongMeta>,
+    next_song_id: SongId,
     song_order: Vec<String>,
     owner: SubjectIdentifier,
+    /// Collaborators besides the owner
+    members: HashMap<SubjectIdentifier, Role>,
+    listeners: Listeners,
+    events: RadioEvents,
+    archive: Archive,
+    recorder: Recorder,
+    /// The stream relayed, `None` for radios playing their songs
+    relay: Option<Relay>,
+    /// Share links of a private radio
+    shares: Vec<Share>,
+}
+/// A song of a radio with its metadata
+#[derive(Debug, Clone, Serialize)]
+pub struct SongRecord {
+    name: String,
+    #[serde(flatten)]
+    meta: SongMeta,
+}
+
+impl RadioState {
+    /// Get a fresh id for a new song, `None` once all are used up
+    fn allocate_song_id(&mut self) -> Option<SongId> {
+        let id = self.next_song_id;
+        self.next_song_id = id.checked_add(1)?;
+        Some(id)
+    }
+
+    /// The role of the user `sub` on the radio, `None` if they aren't a member
+    fn role(&self, sub: &SubjectIdentifier) -> Option<Role> {
+        if *sub == self.owner {
+            return Some(Role::Owner);
+        }
+        self.members.get(sub).copied()
+    }
+
+    /// The name and metadata of the song with id `song`
+    fn song_record(&self, song: SongId) -> Option<SongRecord> {
+        let name = self.song_map.iter().find(|(_, &id)| id == song)?.0;
+        Some(SongRecord {
+            name: name.clone(),
+            meta: self.song_meta.get(&song).cloned().unwrap_or_default(),
+        })
+    }
 }
 /// Serializable Data for saving radio state
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct PersistentRadioState {
     config: SentConfig,
-    song_map: HashMap<String, u8>,
+    song_map: HashMap<String, SongId>,
+    song_meta: HashMap<SongId, SongMeta>,
+    next_song_id: SongId,
     song_order: Vec<String>,
     owner: SubjectIdentifier,
+    recording_schedule: Option<RecordingSchedule>,
+    /// URL of the stream relayed
+    relay: Option<String>,
+    shares: Vec<Share>,
+    members: HashMap<SubjectIdentifier, Role>,
+}
+/// A signed up user
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct User {
+    /// Radios the user owns
+    radios: Vec<String>,
+    /// Radios the user follows, in the order followed
+    favorites: Vec<String>,
+    role: GlobalRole,
 }
 /// Global async app state
 #[derive(Debug)]
 pub struct AppState {
-    pages: RwLock<[String; 5]>,
+    pages: RwLock<[Template; 5]>,
     to_blocking: tokio::sync::mpsc::UnboundedSender<ToBlocking>,
     radio_states: RwLock<HashMap<String, RwLock<RadioState>>>,
     oidc_client: Arc<OidcClient>,
-    users: RwLock<HashMap<SubjectIdentifier, Vec<String>>>,
+    users: RwLock<HashMap<SubjectIdentifier, User>>,
+    storage: Arc<dyn Storage>,
+    quotas: Quotas,
+    max_upload_size: u64,
+    max_import_size: u64,
+    /// Where uploads are kept until ingested, always on the local disk
+    upload_dir: PathBuf,
+    uploads: Uploads,
+    timeshift_window: u64,
+    /// Where the time-shift archives are kept, always on the local disk
+    timeshift_dir: PathBuf,
+    search: SearchIndex,
 }
 /// Serializeble app state
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct PersistentAppState {
     radio_states: HashMap<String, PersistentRadioState>,
-    users: HashMap<SubjectIdentifier, Vec<String>>,
+    users: HashMap<SubjectIdentifier, User>,
 }
 
 struct CliListener {
@@ -163,7 +349,10 @@ impl CliListener {
         };
 
         radio_lock.song_map.remove(&song);
+        radio_lock.song_meta.remove(&song_id);
         radio_lock.song_order.retain(|name| name != &song);
+        radio_lock.events.publish(EventKind::SongRemoved, &song);
+        self.state.search.index_radio(&radio, &radio_lock);
 
         let Ok(()) = self.state.to_blocking.send(ToBlocking::Remove {
             radio: radio.clone(),
@@ -179,6 +368,8 @@ impl CliListener {
         let Some(_) = radios_lock.remove(&radio) else {
             return format!("Can't remove radio {radio} because it doesn't exist");
         };
+        self.state.search.remove_radio(&radio);
+        forget_radio(&self.state, &radio).await;
         let Ok(()) = self.state.to_blocking.send(ToBlocking::RemoveRadio {
             radio: radio.clone(),
         }) else {
@@ -197,37 +388,100 @@ impl CliListener {
         else {
             return format!("Err! No user with sub: {sub}");
         };
+        drop_memberships(
+            &*self.state.radio_states.read().await,
+            &SubjectIdentifier::new(sub.clone()),
+        )
+        .await;
         format!("Removed user with sub: {sub}")
     }
     async fn add_user(&self, sub: String) -> String {
+        let role = self
+            .state
+            .users
+            .write()
+            .await
+            .entry(SubjectIdentifier::new(sub.clone()))
+            .or_default()
+            .role;
         let Ok(token) = jsonwebtoken::encode(
             &self.state.oidc_client.header,
             &auth::Claims {
                 sub: SubjectIdentifier::new(sub.clone()),
                 exp: jsonwebtoken::get_current_timestamp() as usize + 7 * 24 * 60 * 60,
+                role,
             },
             &self.state.oidc_client.encoding_key,
         ) else {
             return format!("Couldn't generate token");
         };
 
-        self.state
-            .users
-            .write()
-            .await
-            .entry(SubjectIdentifier::new(sub.clone()))
-            .or_insert(vec![]);
         format!("Added user with sub {sub}. Token: {token}")
     }
+    async fn grant_role(&self, sub: String, role: String) -> String {
+        let role = match role.parse::<GlobalRole>() {
+            Ok(role) => role,
+            Err(e) => return format!("Err! {e}"),
+        };
+        let mut users = self.state.users.write().await;
+        let Some(user) = users.get_mut(&SubjectIdentifier::new(sub.clone())) else {
+            return format!("Err! No user with sub: {sub}");
+        };
+        user.role = role;
+        format!("User with sub {sub} is now {role:?}, tokens issued before show the old role")
+    }
+    async fn revoke_role(&self, sub: String) -> String {
+        let mut users = self.state.users.write().await;
+        let Some(user) = users.get_mut(&SubjectIdentifier::new(sub.clone())) else {
+            return format!("Err! No user with sub: {sub}");
+        };
+        let role = std::mem::take(&mut user.role);
+        format!("Revoked {role:?} from user with sub {sub}")
+    }
     async fn list_users(&self) -> Vec<String> {
         self.state
             .users
             .read()
             .await
             .iter()
-            .map(|(key, val)| format!("Sub: {}, radios: {val:?}", key.to_string()))
+            .map(|(key, val)| {
+                format!(
+                    "Sub: {}, role: {:?}, radios: {:?}, favorites: {:?}",
+                    key.to_string(),
+                    val.role,
+                    val.radios,
+                    val.favorites
+                )
+            })
             .collect_vec()
     }
+    async fn usage(&self, sub: String) -> String {
+        let radios_lock = self.state.radio_states.read().await;
+        // Users are locked after radios, so not held while reading them
+        let Some(radios) = self
+            .state
+            .users
+            .read()
+            .await
+            .get(&SubjectIdentifier::new(sub.clone()))
+            .map(|user| user.radios.clone())
+        else {
+            return format!("Err! No user with sub: {sub}");
+        };
+        let usage = quotas::Usage::of(&radios_lock, &radios, None).await;
+        let quotas = self.state.quotas;
+        format!(
+            "Radios: {}/{:?}, songs: {:?}/{:?}, bytes: {}/{:?}, duration: {:.0}s/{:?}",
+            usage.radios,
+            quotas.max_radios,
+            usage.songs,
+            quotas.max_songs,
+            usage.bytes,
+            quotas.max_bytes,
+            usage.duration,
+            quotas.max_duration
+        )
+    }
     async fn count_users(&self) -> usize {
         self.state.users.read().await.len()
     }
@@ -248,6 +502,33 @@ impl CliListener {
         let res = radio_lock.read().await.song_map.keys().cloned().collect();
         res
     }
+    async fn list_listeners(&self) -> Vec<String> {
+        let radios_lock = self.state.radio_states.read().await;
+        let mut res = vec![];
+        for (name, radio_lock) in radios_lock.iter() {
+            let counts = radio_lock.read().await.listeners.counts();
+            res.push(format!(
+                "{name}: {} (main: {}, bands: {:?})",
+                counts.total, counts.main, counts.bands
+            ));
+        }
+        res
+    }
+    async fn list_sessions(&self, radio: String) -> Vec<String> {
+        let radios_lock = self.state.radio_states.read().await;
+        let Some(radio_lock) = radios_lock.get(&radio) else {
+            return vec![];
+        };
+        let res = radio_lock
+            .read()
+            .await
+            .listeners
+            .sessions()
+            .into_iter()
+            .map(|session| format!("{session:?}"))
+            .collect();
+        res
+    }
     async fn reload_pages(&self, path: PathBuf) -> String {
         let (tx, rx) = oneshot::channel();
         let Ok(()) = self.reload_pages.send((path.clone(), tx)) else {
@@ -338,8 +619,17 @@ async fn save_state(data: Arc<AppState>, data_dir: PathBuf) {
             config,
             stream: _,
             song_map,
+            song_meta,
+            next_song_id,
             song_order,
             owner,
+            members,
+            listeners: _,
+            events: _,
+            archive: _,
+            recorder,
+            relay,
+            shares,
         } = radio_state.get_mut().clone();
         persistent_radio_states.insert(
             name.clone(),
@@ -347,10 +637,18 @@ async fn save_state(data: Arc<AppState>, data_dir: PathBuf) {
                 config: SentConfig {
                     title: config.title.into(),
                     description: config.description.into(),
+                    tags: config.tags,
+                    visibility: config.visibility,
                 },
                 song_map,
+                song_meta,
+                next_song_id,
                 song_order,
                 owner,
+                recording_schedule: recorder.schedule(),
+                relay: relay.map(|relay| relay.url),
+                shares,
+                members,
             },
         );
     }
@@ -358,12 +656,37 @@ async fn save_state(data: Arc<AppState>, data_dir: PathBuf) {
         radio_states: persistent_radio_states,
         users: data.users.read().await.clone(),
     };
-    let state_buf = postcard::to_allocvec(&state).unwrap();
+    let state_buf = state::encode(&state).unwrap();
     tokio::fs::write(data_dir.join("state"), state_buf)
         .await
         .unwrap();
 }
 
+/// Apply the results of the blocking thread to the app state
+async fn from_blocking(state: Arc<AppState>, mut rx: mpsc::UnboundedReceiver<FromBlocking>) {
+    while let Some(msg) = rx.recv().await {
+        match msg {
+            FromBlocking::Ingested { radio, song, meta } => {
+                let radio_states = state.radio_states.read().await;
+                let Some(radio_state) = radio_states.get(&radio) else {
+                    continue;
+                };
+                let mut radio_state = radio_state.write().await;
+                // The song may have been removed while transcoding
+                let Some(song_meta) = radio_state.song_meta.get_mut(&song) else {
+                    continue;
+                };
+                song_meta.fill_from(meta);
+                let Some(record) = radio_state.song_record(song) else {
+                    continue;
+                };
+                radio_state.events.publish(EventKind::SongUpdated, record);
+                state.search.index_radio(&radio, &radio_state);
+            }
+        }
+    }
+}
+
 fn main() -> std::io::Result<()> {
     let args = Args::parse();
     let port = args.port.unwrap_or(8080);
@@ -382,9 +705,12 @@ fn main() -> std::io::Result<()> {
             let pages = load_pages(args.working_dir.clone()).await?;
             // Create Channels for communication between blocking and async
             let (stx, srx) = unbounded_channel();
+            let (atx, arx) = unbounded_channel();
 
             let oidc_client = Arc::new(OidcClient::new().await);
 
+            let data_dir = args.working_dir.clone().map(|d| d.join("data")).unwrap_or(PathBuf::from("./data"));
+            let storage: Arc<dyn Storage> = storage::from_env(data_dir.clone()).into();
             // Create AppState
             let data: Arc<AppState> = Arc::new(AppState {
                 pages: RwLock::new(pages),
@@ -392,63 +718,157 @@ fn main() -> std::io::Result<()> {
                 radio_states: RwLock::new(HashMap::new()),
                 oidc_client,
                 users: RwLock::new(HashMap::new()),
+                storage: storage.clone(),
+                quotas: args.quotas,
+                max_upload_size: args.max_upload_size,
+                max_import_size: args.max_import_size,
+                upload_dir: data_dir.join(UPLOADS_DIR),
+                uploads: Uploads::default(),
+                timeshift_window: args.timeshift_window,
+                timeshift_dir: data_dir.join(TIMESHIFT_DIR),
+                search: SearchIndex::default(),
             });
 
-            let data_dir = args.working_dir.clone().map(|d| d.join("data")).unwrap_or(PathBuf::from("./data"));
             // Load radio state
             let mut blocking_radio_map = HashMap::new();
-            if let Ok(state_file) = tokio::fs::read(data_dir.join("state")).await {
-                let loaded_state: PersistentAppState =
-                    postcard::from_bytes(&state_file).expect("State file has invalid data!");
-                for (
-                    name,
-                    PersistentRadioState {
-                        config,
-                        song_map,
-                        song_order,
-                        owner
-                    },
-                ) in loaded_state.radio_states.into_iter()
-                {
-                    let (tx, rx) = watch::channel((vec![], [(); NUM_BANDWIDTHS].map(|_| vec![])));
-                    blocking_radio_map.insert(
-                        name.clone(),
-                        (
-                            song_order
-                                .iter()
-                                .filter_map(|song| song_map.get(song).copied())
-                                .collect(),
-                            tx,
-                        ),
-                    );
-                    data.radio_states.write().await.insert(
-                        name,
-                        RwLock::new(RadioState {
-                            config: Config {
-                                title: config.title.into(),
-                                description: config.description.into(),
-                            },
-                            stream: rx,
-                            song_map,
-                            song_order,
-                            owner
-                        }),
-                    );
+            let mut loaded_state = match tokio::fs::read(data_dir.join("state")).await {
+                Ok(state_file) => state::decode(&state_file).expect("State file has invalid data!"),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => PersistentAppState::default(),
+                // Starting empty would remove all songs as unreferenced
+                Err(e) => return Err(e),
+            };
+            state::migrate_layout(&data_dir, &mut loaded_state).await?;
+            // Before anything is cleaned up, which radios with these ids would lose data to
+            let storage_clone = storage.clone();
+            let mut loaded_state = tokio::task::spawn_blocking(move || {
+                state::rename_reserved(&*storage_clone, &mut loaded_state).map(|()| loaded_state)
+            })
+            .await??;
+            // Radios removed before they were dropped from their owners
+            for user in loaded_state.users.values_mut() {
+                user.radios = user
+                    .radios
+                    .iter()
+                    .filter(|radio| loaded_state.radio_states.contains_key(*radio))
+                    .unique()
+                    .cloned()
+                    .collect();
+            }
+            // Uploads left over from a previous run can't be ingested anymore
+            if let Err(e) = tokio::fs::remove_dir_all(&data.upload_dir).await {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    return Err(e);
                 }
-                *data.users.write().await = loaded_state.users;
             }
+            tokio::fs::create_dir_all(&data.upload_dir).await?;
+            // All songs, for the blocking thread to find their blobs
+            let songs = loaded_state
+                .radio_states
+                .iter()
+                .flat_map(|(name, radio)| {
+                    radio.song_map.values().map(|&song| (name.clone(), song))
+                })
+                .collect_vec();
+            // Segment numbers start over, so old archives can't be continued
+            if let Err(e) = tokio::fs::remove_dir_all(&data.timeshift_dir).await {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    return Err(e);
+                }
+            }
+            for (
+                name,
+                PersistentRadioState {
+                    config,
+                    song_map,
+                    song_meta,
+                    next_song_id,
+                    song_order,
+                    owner,
+                    recording_schedule,
+                    relay,
+                    shares,
+                    members,
+                },
+            ) in loaded_state.radio_states.into_iter()
+            {
+                let (tx, rx) = watch::channel(Segment::default());
+                let events = RadioEvents::default();
+                tokio::spawn(events::now_playing_task(
+                    data.clone(),
+                    name.clone(),
+                    rx.clone(),
+                    events.clone(),
+                ));
+                let archive = Archive::new(data.timeshift_dir.join(&name), data.timeshift_window);
+                tokio::spawn(timeshift::archive_task(rx.clone(), archive.clone()));
+                let recorder = Recorder::new(recording_schedule);
+                let listeners = Listeners::new(events.clone());
+                tokio::spawn(recordings::record_task(
+                    rx.clone(),
+                    recorder.clone(),
+                    storage.clone(),
+                    name.clone(),
+                ));
+                // Relay radios are fed by their relay, not scheduled by the blocking thread
+                let relay = match relay {
+                    Some(url) => Some(Relay::start(url, tx, listeners.clone(), recorder.clone())),
+                    None => {
+                        blocking_radio_map.insert(
+                            name.clone(),
+                            (
+                                song_order
+                                    .iter()
+                                    .filter_map(|song| song_map.get(song).copied())
+                                    .collect(),
+                                tx,
+                            ),
+                        );
+                        None
+                    }
+                };
+                let radio_state = RadioState {
+                    config: Config {
+                        title: config.title.into(),
+                        description: config.description.into(),
+                        tags: config.tags,
+                        visibility: config.visibility,
+                    },
+                    stream: rx,
+                    song_map,
+                    song_meta,
+                    next_song_id,
+                    song_order,
+                    owner,
+                    members,
+                    listeners,
+                    events,
+                    archive,
+                    recorder,
+                    relay,
+                    shares,
+                };
+                data.search.index_radio(&name, &radio_state);
+                data.radio_states
+                    .write()
+                    .await
+                    .insert(name, RwLock::new(radio_state));
+            }
+            *data.users.write().await = loaded_state.users;
 
             // Start blocking thread
-            let blocking_data_dir = data_dir.clone();
             std::thread::spawn(|| {
                 blocking::main(
                     srx,
+                    atx,
                     Duration::from_secs(10),
                     blocking_radio_map,
-                    blocking_data_dir,
+                    songs,
+                    storage,
                 )
             });
 
+            tokio::spawn(from_blocking(data.clone(), arx));
+
             // Start web server task
             let server = {
                 let data = data.clone();
@@ -458,6 +878,11 @@ fn main() -> std::io::Result<()> {
                         .app_data(web::Data::new(data.clone()))
                         .wrap(Compress::default())
                         .service(get_search_page)
+                        .service(get_tag_page)
+                        .service(get_favorites)
+                        .service(add_favorite)
+                        .service(remove_favorite)
+                        .service(get_user_events)
                         .service(get_start_page)
                         .service(get_auth_page)
                         .service(get_radio_page)
@@ -474,6 +899,33 @@ fn main() -> std::io::Result<()> {
                         .service(remove_song)
                         .service(get_audio)
                         .service(get_audio_band)
+                        .service(get_listeners)
+                        .service(get_now_playing)
+                        .service(get_events)
+                        .service(get_song_cover)
+                        .service(get_now_playing_cover)
+                        .service(get_usage)
+                        .service(get_upload_options)
+                        .service(create_upload)
+                        .service(get_upload_offset)
+                        .service(upload_chunk)
+                        .service(remove_upload)
+                        .service(import_songs)
+                        .service(edit_song)
+                        .service(get_song_peaks)
+                        .service(get_song_preview)
+                        .service(get_recordings)
+                        .service(start_recording)
+                        .service(stop_recording)
+                        .service(set_recording_schedule)
+                        .service(get_recording)
+                        .service(remove_recording)
+                        .service(get_shares)
+                        .service(create_share)
+                        .service(revoke_share)
+                        .service(get_members)
+                        .service(set_member)
+                        .service(remove_member)
                         .service(google_redirect)
                         .service(google_callback)
                         .service(Files::new("/reserved", (*working_dir).clone().unwrap_or(PathBuf::from(".")).join("resources")).prefer_utf8(true))
@@ -525,31 +977,27 @@ fn main() -> std::io::Result<()> {
         })
 }
 
-async fn load_pages(pages: Option<PathBuf>) -> std::io::Result<[String; 5]> {
-    Ok({
-        let path = pages.unwrap_or(PathBuf::from(".")).join("resources");
-        // Read all files
-        let files = join_all([
-            read_to_string(path.join("start.html")),
-            read_to_string(path.join("radio.html")),
-            read_to_string(path.join("edit.html")),
-            read_to_string(path.join("login.html")),
-            read_to_string(path.join("settings.html")),
-        ])
-        .await
-        .into_iter()
-        .collect::<Result<Box<[String]>, _>>()?;
-        [
-            files[0].clone(),
-            files[1].clone(),
-            files[2].clone(),
-            files[3].clone(),
-            files[4].clone(),
-        ]
-    }
-    .map(|s| {
-        s.replace("./", "/reserved/")
+async fn load_pages(pages: Option<PathBuf>) -> std::io::Result<[Template; 5]> {
+    let path = pages.unwrap_or(PathBuf::from(".")).join("resources");
+    let names = [
+        "start.html",
+        "radio.html",
+        "edit.html",
+        "login.html",
+        "settings.html",
+    ];
+    // Read all files
+    let files = join_all(names.map(|name| read_to_string(path.join(name)))).await;
+    let mut pages = Vec::with_capacity(names.len());
+    for (name, file) in names.into_iter().zip(files) {
+        let page = file?
+            .replace("./", "/reserved/")
             .replace("start.html", "/")
-            .replace("login.html", "/auth")
-    }))
+            .replace("login.html", "/auth");
+        let page = Template::parse(&page).map_err(|e| {
+            std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{name}, {e}"))
+        })?;
+        pages.push(page);
+    }
+    Ok(pages.try_into().expect("a page for each name"))
 }
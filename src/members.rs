@@ -0,0 +1,24 @@
+use openidconnect::SubjectIdentifier;
+use serde::{Deserialize, Serialize};
+
+/// Most collaborators a radio can have besides its owner
+pub const MAX_MEMBERS: usize = 50;
+
+/// What a user may do with a radio, each role may do all the lower ones can
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Changes the song order
+    Scheduler,
+    /// Uploads and edits songs, records and changes the config
+    Editor,
+    /// Removes the radio, shares it and manages its members
+    Owner,
+}
+
+/// A user and their role on a radio
+#[derive(Debug, Clone, Serialize)]
+pub struct Member {
+    pub sub: SubjectIdentifier,
+    pub role: Role,
+}
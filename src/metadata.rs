@@ -0,0 +1,173 @@
+use id3::TagLike;
+use serde::{Deserialize, Serialize};
+use symphonia::core::meta::{MetadataRevision, StandardTagKey, StandardVisualKey, Value};
+
+/// Descriptive data of a song, read from its tags at ingest
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct SongMeta {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub track: Option<u32>,
+    pub year: Option<i32>,
+    pub genre: Option<String>,
+    /// Length in seconds, known once the song is transcoded
+    pub duration: Option<f64>,
+    /// Size in bytes of the uploaded file
+    pub size: Option<u64>,
+}
+
+/// Changes to the metadata of a song, `null` clears a field
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct MetaPatch {
+    #[serde(default, deserialize_with = "some")]
+    pub title: Option<Option<String>>,
+    #[serde(default, deserialize_with = "some")]
+    pub artist: Option<Option<String>>,
+    #[serde(default, deserialize_with = "some")]
+    pub album: Option<Option<String>>,
+    #[serde(default, deserialize_with = "some")]
+    pub track: Option<Option<u32>>,
+    #[serde(default, deserialize_with = "some")]
+    pub year: Option<Option<i32>>,
+    #[serde(default, deserialize_with = "some")]
+    pub genre: Option<Option<String>>,
+}
+
+/// Tell a present `null` apart from a missing field
+fn some<'de, D: serde::Deserializer<'de>, T: Deserialize<'de>>(
+    deserializer: D,
+) -> Result<Option<T>, D::Error> {
+    T::deserialize(deserializer).map(Some)
+}
+
+/// Parse the leading number of a value like `3/12` or `2004-05-01`
+fn leading_number<T: std::str::FromStr>(value: &str) -> Option<T> {
+    let value = value.trim();
+    let end = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    value[..end].parse().ok()
+}
+
+fn text(value: &Value) -> Option<String> {
+    let text = value.to_string();
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_owned())
+}
+
+impl SongMeta {
+    /// Fill the fields not yet known from a symphonia metadata revision
+    pub fn fill_from_revision(&mut self, revision: &MetadataRevision) {
+        for tag in revision.tags() {
+            let Some(key) = tag.std_key else {
+                continue;
+            };
+            match key {
+                StandardTagKey::TrackTitle if self.title.is_none() => self.title = text(&tag.value),
+                StandardTagKey::Artist if self.artist.is_none() => self.artist = text(&tag.value),
+                StandardTagKey::Album if self.album.is_none() => self.album = text(&tag.value),
+                StandardTagKey::Genre if self.genre.is_none() => self.genre = text(&tag.value),
+                StandardTagKey::TrackNumber if self.track.is_none() => {
+                    self.track = text(&tag.value).and_then(|v| leading_number(&v))
+                }
+                StandardTagKey::Date
+                | StandardTagKey::ReleaseDate
+                | StandardTagKey::OriginalDate
+                    if self.year.is_none() =>
+                {
+                    self.year = text(&tag.value).and_then(|v| leading_number(&v))
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Fill the fields not yet known from an ID3 tag
+    pub fn fill_from_id3(&mut self, tag: &id3::Tag) {
+        self.title = self.title.take().or(tag.title().map(str::to_owned));
+        self.artist = self.artist.take().or(tag.artist().map(str::to_owned));
+        self.album = self.album.take().or(tag.album().map(str::to_owned));
+        self.track = self.track.or(tag.track());
+        self.year = self
+            .year
+            .or(tag.year())
+            .or(tag.date_recorded().map(|date| date.year));
+        self.genre = self
+            .genre
+            .take()
+            .or(tag.genre_parsed().map(|genre| genre.into_owned()));
+    }
+
+    /// Set the fields given in a patch
+    pub fn apply(&mut self, patch: MetaPatch) {
+        let MetaPatch {
+            title,
+            artist,
+            album,
+            track,
+            year,
+            genre,
+        } = patch;
+        if let Some(title) = title {
+            self.title = title;
+        }
+        if let Some(artist) = artist {
+            self.artist = artist;
+        }
+        if let Some(album) = album {
+            self.album = album;
+        }
+        if let Some(track) = track {
+            self.track = track;
+        }
+        if let Some(year) = year {
+            self.year = year;
+        }
+        if let Some(genre) = genre {
+            self.genre = genre;
+        }
+    }
+
+    /// Fill the fields not yet known from `other`
+    pub fn fill_from(&mut self, other: SongMeta) {
+        self.title = self.title.take().or(other.title);
+        self.artist = self.artist.take().or(other.artist);
+        self.album = self.album.take().or(other.album);
+        self.track = self.track.or(other.track);
+        self.year = self.year.or(other.year);
+        self.genre = self.genre.take().or(other.genre);
+        self.duration = self.duration.or(other.duration);
+        self.size = self.size.or(other.size);
+    }
+}
+
+/// The front cover of a revision, or the first picture if none is marked as such
+pub fn cover_from_revision(revision: &MetadataRevision) -> Option<&[u8]> {
+    let visuals = revision.visuals();
+    visuals
+        .iter()
+        .find(|visual| visual.usage == Some(StandardVisualKey::FrontCover))
+        .or(visuals.first())
+        .map(|visual| &*visual.data)
+}
+
+/// The front cover of an ID3 tag (APIC), or the first picture if none is marked as such
+pub fn cover_from_id3(tag: &id3::Tag) -> Option<&[u8]> {
+    tag.pictures()
+        .find(|picture| picture.picture_type == id3::frame::PictureType::CoverFront)
+        .or(tag.pictures().next())
+        .map(|picture| &*picture.data)
+}
+
+/// Guess the media type of an image from its first bytes
+pub fn image_type(data: &[u8]) -> &'static str {
+    match data {
+        [0xFF, 0xD8, 0xFF, ..] => "image/jpeg",
+        [0x89, b'P', b'N', b'G', ..] => "image/png",
+        [b'G', b'I', b'F', b'8', ..] => "image/gif",
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => "image/webp",
+        [b'B', b'M', ..] => "image/bmp",
+        _ => "application/octet-stream",
+    }
+}
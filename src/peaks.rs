@@ -0,0 +1,70 @@
+use byteorder::{ReadBytesExt, WriteBytesExt, LE};
+use serde::Serialize;
+use std::io::{self, Cursor};
+
+/// Marks a peaks file
+const MAGIC: &[u8; 4] = b"PEAK";
+/// Version of the peaks file layout
+const VERSION: u32 = 1;
+/// Min/max pairs per second of audio
+pub const PEAKS_PER_SECOND: u32 = 50;
+
+/// Downsampled waveform of a song, for drawing
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Peaks {
+    /// Pairs per second
+    pub rate: u32,
+    /// Minimum and maximum sample of each window, over all channels
+    pub data: Vec<(i16, i16)>,
+}
+
+impl Peaks {
+    /// Compute the peaks of interleaved samples
+    pub fn compute(pcm: &[i16], rate: usize, num_channels: usize) -> Self {
+        let window = (rate / PEAKS_PER_SECOND as usize).max(1) * num_channels;
+        Self {
+            rate: PEAKS_PER_SECOND,
+            data: pcm
+                .chunks(window)
+                .map(|chunk| {
+                    chunk
+                        .iter()
+                        .fold((i16::MAX, i16::MIN), |(min, max), &sample| {
+                            (min.min(sample), max.max(sample))
+                        })
+                })
+                .collect(),
+        }
+    }
+
+    /// Encode as stored and served in binary: magic, version, rate and count (u32 LE),
+    /// then the min and max of each pair (i16 LE)
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = MAGIC.to_vec();
+        buf.reserve(12 + self.data.len() * 4);
+        // Writing to a Vec can't fail
+        buf.write_u32::<LE>(VERSION).unwrap();
+        buf.write_u32::<LE>(self.rate).unwrap();
+        buf.write_u32::<LE>(self.data.len() as u32).unwrap();
+        for &(min, max) in &self.data {
+            buf.write_i16::<LE>(min).unwrap();
+            buf.write_i16::<LE>(max).unwrap();
+        }
+        buf
+    }
+
+    pub fn decode(buf: &[u8]) -> io::Result<Self> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "Not a peaks file");
+        let mut buf = Cursor::new(buf.strip_prefix(MAGIC).ok_or_else(invalid)?);
+        if buf.read_u32::<LE>()? != VERSION {
+            return Err(invalid());
+        }
+        let rate = buf.read_u32::<LE>()?;
+        let count = buf.read_u32::<LE>()? as usize;
+        let mut data = Vec::with_capacity(count.min(buf.get_ref().len() / 4));
+        for _ in 0..count {
+            data.push((buf.read_i16::<LE>()?, buf.read_i16::<LE>()?));
+        }
+        Ok(Self { rate, data })
+    }
+}
@@ -0,0 +1,100 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use tokio::sync::RwLock;
+
+use crate::{errors::PageError, RadioState};
+
+/// Limits on what users can store, unlimited where not given
+#[derive(Debug, Clone, Copy, Default, clap::Args, Serialize)]
+pub struct Quotas {
+    /// Maximum number of radios per user
+    #[arg(long)]
+    pub max_radios: Option<usize>,
+    /// Maximum number of songs per radio
+    #[arg(long)]
+    pub max_songs: Option<usize>,
+    /// Maximum size in bytes of the songs uploaded to the radios of a user
+    #[arg(long)]
+    pub max_bytes: Option<u64>,
+    /// Maximum length in seconds of the songs in the radios of a user
+    #[arg(long)]
+    pub max_duration: Option<f64>,
+}
+
+/// What a user stores
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Usage {
+    pub radios: usize,
+    /// Number of songs of each radio
+    pub songs: BTreeMap<String, usize>,
+    pub bytes: u64,
+    /// Seconds, of the songs transcoded so far
+    pub duration: f64,
+}
+
+/// Usage of a user together with the limits
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageReport {
+    pub usage: Usage,
+    pub quotas: Quotas,
+}
+
+impl Usage {
+    /// Count in a radio of the user
+    pub fn add_radio(&mut self, name: &str, radio_state: &RadioState) {
+        self.radios += 1;
+        self.songs
+            .insert(name.to_owned(), radio_state.song_map.len());
+        for meta in radio_state.song_meta.values() {
+            self.bytes += meta.size.unwrap_or(0);
+            self.duration += meta.duration.unwrap_or(0.0);
+        }
+    }
+
+    /// Usage of `radios` but `except`, locking one radio at a time, so the caller must not
+    /// hold any (it adds the one it's going to lock itself with `add_radio`)
+    pub async fn of(
+        radio_states: &HashMap<String, RwLock<RadioState>>,
+        radios: &[String],
+        except: Option<&str>,
+    ) -> Self {
+        let mut usage = Self::default();
+        for name in radios {
+            if except == Some(name.as_str()) {
+                continue;
+            }
+            let Some(radio_state) = radio_states.get(name) else {
+                continue;
+            };
+            usage.add_radio(name, &*radio_state.read().await);
+        }
+        usage
+    }
+}
+
+impl Quotas {
+    /// Check that a user can add another radio
+    pub fn check_radios(&self, radios: usize) -> Result<(), PageError> {
+        match self.max_radios {
+            Some(max) if radios >= max => Err(PageError::QuotaExceeded("radios")),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check that another song can be added to a radio
+    pub fn check_songs(&self, usage: &Usage, radio: &str) -> Result<(), PageError> {
+        let songs = usage.songs.get(radio).copied().unwrap_or(0);
+        if self.max_songs.is_some_and(|max| songs >= max) {
+            return Err(PageError::QuotaExceeded("songs"));
+        }
+        match self.max_duration {
+            Some(max) if usage.duration >= max => Err(PageError::QuotaExceeded("duration")),
+            _ => Ok(()),
+        }
+    }
+
+    /// Bytes a user can still upload, `None` if unlimited
+    pub fn remaining_bytes(&self, usage: &Usage) -> Option<u64> {
+        self.max_bytes.map(|max| max.saturating_sub(usage.bytes))
+    }
+}
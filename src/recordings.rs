@@ -0,0 +1,228 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::watch;
+
+use crate::{blocking::Segment, listeners::unix_now, storage::Storage};
+
+/// Directory of the recordings, inside the radio's directory
+pub const RECORDINGS_DIR: &str = "recordings";
+
+/// Recordings repeating at a fixed interval
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RecordingSchedule {
+    /// Unix time the first recording starts
+    pub start: u64,
+    /// Seconds from the start of one recording to the next, like 86400 for daily
+    pub every: u64,
+    /// Seconds each recording lasts
+    pub duration: u64,
+}
+
+impl RecordingSchedule {
+    pub fn is_valid(&self) -> bool {
+        self.duration > 0 && self.duration <= self.every
+    }
+
+    /// Start and end of the scheduled recording running at `now`, if any
+    fn window(&self, now: u64) -> Option<(u64, u64)> {
+        if now < self.start || self.every == 0 {
+            return None;
+        }
+        let start = now - (now - self.start) % self.every;
+        (now < start + self.duration).then_some((start, start + self.duration))
+    }
+}
+
+/// A recording in progress
+#[derive(Debug, Clone, Serialize)]
+pub struct ActiveRecording {
+    pub name: String,
+    /// Unix time
+    pub started: u64,
+    /// Unix time it stops by itself, `None` if only stopped by hand
+    pub until: Option<u64>,
+}
+
+#[derive(Debug, Default)]
+struct RecorderState {
+    current: Option<ActiveRecording>,
+    schedule: Option<RecordingSchedule>,
+    /// End of a scheduled recording stopped by hand, so it isn't started again
+    skip_until: u64,
+    /// Start of the last recording, keeping names unique
+    last_started: u64,
+}
+
+/// Start/stop controls and schedule of the recordings of a radio
+/// (cheap to clone, shared between handlers)
+#[derive(Debug, Clone)]
+pub struct Recorder(Arc<Mutex<RecorderState>>);
+
+impl Recorder {
+    pub fn new(schedule: Option<RecordingSchedule>) -> Self {
+        Self(Arc::new(Mutex::new(RecorderState {
+            schedule,
+            ..Default::default()
+        })))
+    }
+
+    pub fn current(&self) -> Option<ActiveRecording> {
+        self.0.lock().unwrap().current.clone()
+    }
+
+    pub fn schedule(&self) -> Option<RecordingSchedule> {
+        self.0.lock().unwrap().schedule
+    }
+
+    pub fn set_schedule(&self, schedule: Option<RecordingSchedule>) {
+        let mut state = self.0.lock().unwrap();
+        state.schedule = schedule;
+        state.skip_until = 0;
+    }
+
+    /// Start recording for `duration` seconds (until stopped if `None`),
+    /// `None` if a recording is already running
+    pub fn start(&self, duration: Option<u64>) -> Option<ActiveRecording> {
+        let mut state = self.0.lock().unwrap();
+        if state.current.is_some() {
+            return None;
+        }
+        let started = unix_now();
+        Some(state.begin(started, duration.map(|duration| started + duration)))
+    }
+
+    /// Stop the running recording, returning it
+    pub fn stop(&self) -> Option<ActiveRecording> {
+        let mut state = self.0.lock().unwrap();
+        let current = state.current.take()?;
+        if let Some(window) = state
+            .schedule
+            .and_then(|schedule| schedule.window(unix_now()))
+        {
+            state.skip_until = window.1;
+        }
+        Some(current)
+    }
+
+    /// Whether a recording runs or is scheduled to run at `now`
+    pub fn is_recording(&self, now: u64) -> bool {
+        let state = self.0.lock().unwrap();
+        state
+            .current
+            .as_ref()
+            .is_some_and(|current| current.until.is_none_or(|until| now < until))
+            || state
+                .schedule
+                .and_then(|schedule| schedule.window(now))
+                .is_some_and(|(_, end)| end > state.skip_until)
+    }
+
+    /// Name of the recording a segment published at `now` belongs to, if any
+    fn poll(&self, now: u64) -> Option<String> {
+        let mut state = self.0.lock().unwrap();
+        if state
+            .current
+            .as_ref()
+            .is_some_and(|current| current.until.is_some_and(|until| now >= until))
+        {
+            state.current = None;
+        }
+        if state.current.is_none() {
+            if let Some((_, end)) = state
+                .schedule
+                .and_then(|schedule| schedule.window(now))
+                .filter(|&(_, end)| end > state.skip_until)
+            {
+                state.begin(now, Some(end));
+            }
+        }
+        state.current.as_ref().map(|current| current.name.clone())
+    }
+}
+
+impl RecorderState {
+    fn begin(&mut self, now: u64, until: Option<u64>) -> ActiveRecording {
+        let started = now.max(self.last_started + 1);
+        self.last_started = started;
+        let recording = ActiveRecording {
+            name: started.to_string(),
+            started,
+            until,
+        };
+        self.current = Some(recording.clone());
+        recording
+    }
+}
+
+/// Key prefix of the recordings of a radio
+pub fn recordings_key(radio: &str) -> String {
+    format!("{radio}/{RECORDINGS_DIR}")
+}
+
+/// A finished or running recording of a radio
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordingInfo {
+    pub name: String,
+    /// Unix time
+    pub started: u64,
+    /// Number of segments recorded so far
+    pub segments: usize,
+}
+
+/// The stored recordings of a radio, oldest first
+pub fn list(storage: &dyn Storage, radio: &str) -> std::io::Result<Vec<RecordingInfo>> {
+    let prefix = recordings_key(radio);
+    let mut recordings = BTreeMap::<u64, usize>::new();
+    for key in storage.list(&prefix)? {
+        let Some(started) = key
+            .strip_prefix(&prefix)
+            .and_then(|key| key.strip_prefix('/'))
+            .and_then(|key| key.split_once('/'))
+            .and_then(|(name, _)| name.parse().ok())
+        else {
+            continue;
+        };
+        *recordings.entry(started).or_default() += 1;
+    }
+    Ok(recordings
+        .into_iter()
+        .map(|(started, segments)| RecordingInfo {
+            name: started.to_string(),
+            started,
+            segments,
+        })
+        .collect())
+}
+
+/// Write what a radio publishes into its recordings, finishes when the radio is removed
+///
+/// Each segment is stored as `{radio}/recordings/{name}/{seq}.aac`, a recording is
+/// served as its segments one after another.
+pub async fn record_task(
+    mut stream: watch::Receiver<Segment>,
+    recorder: Recorder,
+    storage: Arc<dyn Storage>,
+    radio: String,
+) {
+    while stream.changed().await.is_ok() {
+        let Some(name) = recorder.poll(unix_now()) else {
+            stream.borrow_and_update();
+            continue;
+        };
+        let (seq, data) = {
+            let segment = stream.borrow_and_update();
+            (segment.seq, segment.data.clone())
+        };
+        let key = format!("{}/{name}/{seq}.aac", recordings_key(&radio));
+        let storage = storage.clone();
+        if let Err(e) = tokio::task::spawn_blocking(move || storage.write(&key, &data))
+            .await
+            .unwrap_or_else(|e| Err(std::io::Error::other(e)))
+        {
+            eprintln!("Couldn't record segment {seq} of {radio}: {e}");
+        }
+    }
+}
@@ -0,0 +1,245 @@
+use std::{io, mem::take, sync::Arc, time::Duration};
+use tokio::{
+    select,
+    sync::{oneshot, watch},
+};
+
+use crate::{
+    blocking::{band_encoders, recode, Segment},
+    listeners::{unix_now, Listeners},
+    recordings::Recorder,
+    NUM_BANDWIDTHS,
+};
+
+/// Seconds of audio in each relayed segment, as in songs
+const SEGMENT_SECS: u64 = 10;
+/// Wait before the first reconnect, doubled on each failure
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// A connection sending nothing for this long is dropped
+const STALL_TIMEOUT: Duration = Duration::from_secs(30);
+/// Seconds without listeners or recordings after which the source is disconnected
+const IDLE_TIMEOUT: u64 = 5 * 60;
+/// How often an idle relay checks whether it is needed
+const IDLE_CHECK: Duration = Duration::from_secs(1);
+/// Bytes without an ADTS frame after which the source isn't taken as AAC
+const MAX_GARBAGE: usize = 1 << 16;
+/// Sample rates by the index in the ADTS header
+const SAMPLE_RATES: [u64; 13] = [
+    96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000, 7350,
+];
+
+/// The source of a relay radio, relaying stops once all clones are dropped
+///
+/// The source is only connected while the radio is listened to or recorded.
+#[derive(Debug, Clone)]
+pub struct Relay {
+    pub url: String,
+    _stop: Arc<oneshot::Sender<()>>,
+}
+
+impl Relay {
+    /// Start relaying the stream at `url` (ADTS AAC, like a jari radio or an Icecast mount)
+    pub fn start(
+        url: String,
+        stream: watch::Sender<Segment>,
+        listeners: Listeners,
+        recorder: Recorder,
+    ) -> Self {
+        let (stop, stopped) = oneshot::channel();
+        tokio::spawn(relay_task(
+            url.clone(),
+            stream,
+            Audience {
+                listeners,
+                recorder,
+            },
+            stopped,
+        ));
+        Self {
+            url,
+            _stop: Arc::new(stop),
+        }
+    }
+}
+
+/// Whether `url` can be relayed
+pub fn is_valid_url(url: &str) -> bool {
+    reqwest::Url::parse(url).is_ok_and(|url| matches!(url.scheme(), "http" | "https"))
+}
+
+/// Splits a stream of ADTS frames into segments of `SEGMENT_SECS`
+#[derive(Debug, Default)]
+struct Segmenter {
+    buf: Vec<u8>,
+    segment: Vec<u8>,
+    /// Samples per channel in `segment`
+    samples: u64,
+    /// Bytes skipped since the last frame
+    skipped: usize,
+}
+
+impl Segmenter {
+    /// Take received bytes, returning the segments completed by them
+    fn push(&mut self, data: &[u8]) -> io::Result<Vec<Vec<u8>>> {
+        self.buf.extend_from_slice(data);
+        let mut segments = vec![];
+        let mut pos = 0;
+        while let Some(header) = self.buf.get(pos..pos + 7) {
+            let len = (usize::from(header[3] & 3) << 11)
+                | (usize::from(header[4]) << 3)
+                | usize::from(header[5] >> 5);
+            // Sync word and layer 0, anything else is skipped until the next frame
+            let is_frame = header[0] == 0xFF && header[1] & 0xF6 == 0xF0 && len >= 7;
+            let Some(&rate) = SAMPLE_RATES
+                .get(usize::from(header[2] >> 2 & 0xF))
+                .filter(|_| is_frame)
+            else {
+                pos += 1;
+                self.skipped += 1;
+                if self.skipped > MAX_GARBAGE {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "Not an ADTS AAC stream",
+                    ));
+                }
+                continue;
+            };
+            let Some(frame) = self.buf.get(pos..pos + len) else {
+                break;
+            };
+            self.segment.extend_from_slice(frame);
+            self.samples += 1024 * (u64::from(header[6] & 3) + 1);
+            self.skipped = 0;
+            pos += len;
+            if self.samples >= rate * SEGMENT_SECS {
+                segments.push(take(&mut self.segment));
+                self.samples = 0;
+            }
+        }
+        self.buf.drain(..pos);
+        Ok(segments)
+    }
+}
+
+/// Relay one connection to the source until it ends, resetting `backoff` once it delivers
+async fn relay(
+    client: &reqwest::Client,
+    url: &str,
+    stream: &watch::Sender<Segment>,
+    backoff: &mut Duration,
+) -> io::Result<()> {
+    let mut res = client
+        .get(url)
+        .send()
+        .await
+        .and_then(|res| res.error_for_status())
+        .map_err(io::Error::other)?;
+    let mut segmenter = Segmenter::default();
+    let mut codecs = Some((
+        band_encoders(44100),
+        fdk_aac::dec::Decoder::new(fdk_aac::dec::Transport::Adts),
+    ));
+    // The codecs are reset for the first segment, the stream doesn't continue the last one
+    let mut new_stream = true;
+    loop {
+        let chunk = tokio::time::timeout(STALL_TIMEOUT, res.chunk())
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "Source stalled"))?
+            .map_err(io::Error::other)?;
+        let Some(chunk) = chunk else {
+            return Ok(());
+        };
+        for data in segmenter.push(&chunk)? {
+            let (mut encoders, mut decoder) = codecs.take().unwrap();
+            let (bands, encoders, decoder, data) = tokio::task::spawn_blocking(move || {
+                let bands = recode(data.clone(), &mut encoders, &mut decoder, new_stream);
+                (bands, encoders, decoder, data)
+            })
+            .await
+            .map_err(io::Error::other)?;
+            codecs = Some((encoders, decoder));
+            let bands: [Vec<u8>; NUM_BANDWIDTHS] =
+                bands.map_err(|_| io::Error::other("Couldn't recode segment"))?;
+            new_stream = false;
+            stream
+                .send(Segment {
+                    data,
+                    bands,
+                    now: None,
+                    seq: stream.borrow().seq + 1,
+                })
+                .map_err(|_| io::Error::other("Radio stream closed"))?;
+            *backoff = MIN_BACKOFF;
+        }
+    }
+}
+
+/// Who may need the relayed stream of a radio
+#[derive(Debug)]
+struct Audience {
+    listeners: Listeners,
+    recorder: Recorder,
+}
+
+impl Audience {
+    fn is_needed(&self, now: u64) -> bool {
+        self.recorder.is_recording(now)
+            || self
+                .listeners
+                .idle_since()
+                .is_none_or(|since| now.saturating_sub(since) < IDLE_TIMEOUT)
+    }
+
+    /// Resolves once the stream hasn't been needed for `IDLE_TIMEOUT`
+    async fn idle(&self) {
+        while self.is_needed(unix_now()) {
+            tokio::time::sleep(IDLE_CHECK).await;
+        }
+    }
+
+    /// Resolves once the stream is needed again
+    async fn needed(&self) {
+        while self.listeners.idle_since().is_some() && !self.recorder.is_recording(unix_now()) {
+            tokio::time::sleep(IDLE_CHECK).await;
+        }
+    }
+}
+
+/// Relay a remote stream into a radio, reconnecting with backoff until stopped
+///
+/// The source is disconnected while nobody needs it and connected again once someone does.
+async fn relay_task(
+    url: String,
+    stream: watch::Sender<Segment>,
+    audience: Audience,
+    mut stopped: oneshot::Receiver<()>,
+) {
+    let client = reqwest::Client::new();
+    let mut backoff = MIN_BACKOFF;
+    loop {
+        let res = select! {
+            biased;
+            _ = &mut stopped => return,
+            () = audience.idle() => {
+                eprintln!("Nobody listens to relayed stream {url}, disconnecting");
+                select! {
+                    () = audience.needed() => {},
+                    _ = &mut stopped => return,
+                }
+                backoff = MIN_BACKOFF;
+                continue;
+            }
+            res = relay(&client, &url, &stream, &mut backoff) => res,
+        };
+        match res {
+            Ok(()) => eprintln!("Relayed stream {url} ended, reconnecting in {backoff:?}"),
+            Err(e) => eprintln!("Couldn't relay stream {url}: {e}, reconnecting in {backoff:?}"),
+        }
+        select! {
+            _ = tokio::time::sleep(backoff) => {},
+            _ = &mut stopped => return,
+        }
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
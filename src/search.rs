@@ -0,0 +1,425 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    ops::Bound,
+    sync::RwLock,
+};
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+use crate::{sharing::Visibility, RadioState, SongId};
+
+/// Weight of a term matching exactly, the others are relative to it
+const EXACT: f32 = 1.0;
+const PREFIX: f32 = 0.6;
+/// Weight of a term with one or two typos
+const FUZZY: [f32; 2] = [0.4, 0.2];
+/// Query terms shorter than this only match exactly
+const MIN_PREFIX_LEN: usize = 2;
+
+/// A radio or a song, as found by a search
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Doc {
+    Radio(String),
+    Song(String, SongId),
+}
+
+impl Doc {
+    /// The radio that is or has the document
+    fn radio(&self) -> &str {
+        match self {
+            Doc::Radio(radio) | Doc::Song(radio, _) => radio,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Index {
+    /// Documents containing each term, with the weight of the best field it is in
+    terms: BTreeMap<String, HashMap<Doc, f32>>,
+    /// Documents and terms indexed for each radio, to remove them again
+    radios: HashMap<String, Vec<(Doc, String)>>,
+    /// Tags of each radio, for filtering
+    tags: HashMap<String, Vec<String>>,
+}
+
+/// Inverted index over the radios and their songs, for searching
+#[derive(Debug, Default)]
+pub struct SearchIndex {
+    inner: RwLock<Index>,
+}
+
+/// Lowercase, without accents and split into words
+fn tokenize(text: &str) -> Vec<String> {
+    let mut folded = String::with_capacity(text.len());
+    for c in text.nfkd().filter(|&c| !is_combining_mark(c)) {
+        match c {
+            'ß' => folded.push_str("ss"),
+            'æ' | 'Æ' => folded.push_str("ae"),
+            'œ' | 'Œ' => folded.push_str("oe"),
+            'ø' | 'Ø' => folded.push('o'),
+            'ł' | 'Ł' => folded.push('l'),
+            'đ' | 'Đ' => folded.push('d'),
+            c => folded.extend(c.to_lowercase()),
+        }
+    }
+    folded
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+/// The text of cleaned markup, without tags and entities
+fn html_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut skip = None;
+    for c in html.chars() {
+        match (skip, c) {
+            (None, '<') => skip = Some('>'),
+            (None, '&') => skip = Some(';'),
+            (None, c) => text.push(c),
+            (Some(end), c) if c == end => {
+                skip = None;
+                text.push(' ');
+            }
+            (Some(_), _) => {}
+        }
+    }
+    text
+}
+
+/// Edit distance of `a` and `b`, `None` if above `max`
+fn distance(a: &[char], b: &[char], max: usize) -> Option<usize> {
+    if a.len().abs_diff(b.len()) > max {
+        return None;
+    }
+    let mut prev = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.iter().enumerate() {
+        let mut row = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            row[j + 1] = (prev[j] + usize::from(ca != cb))
+                .min(prev[j + 1] + 1)
+                .min(row[j] + 1);
+        }
+        if row.iter().all(|&d| d > max) {
+            return None;
+        }
+        prev = row;
+    }
+    Some(prev[b.len()]).filter(|&d| d <= max)
+}
+
+/// Typos allowed in a query term
+fn max_typos(term: &str) -> usize {
+    match term.chars().count() {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+impl Index {
+    fn add(&mut self, radio: &str, doc: &Doc, text: &str, weight: f32) {
+        for term in tokenize(text) {
+            let best = self
+                .terms
+                .entry(term.clone())
+                .or_default()
+                .entry(doc.clone())
+                .or_default();
+            *best = best.max(weight);
+            self.radios
+                .entry(radio.to_owned())
+                .or_default()
+                .push((doc.clone(), term));
+        }
+    }
+
+    fn remove_radio(&mut self, radio: &str) {
+        self.tags.remove(radio);
+        for (doc, term) in self.radios.remove(radio).unwrap_or_default() {
+            if let Some(docs) = self.terms.get_mut(&term) {
+                docs.remove(&doc);
+                if docs.is_empty() {
+                    self.terms.remove(&term);
+                }
+            }
+        }
+    }
+
+    /// Terms matching a query term, with how well they match
+    fn matches(&self, query: &str) -> Vec<(&str, f32)> {
+        let mut matches = vec![];
+        if query.chars().count() >= MIN_PREFIX_LEN {
+            matches.extend(
+                self.terms
+                    .range::<str, _>((Bound::Included(query), Bound::Unbounded))
+                    .take_while(|(term, _)| term.starts_with(query))
+                    .map(|(term, _)| (term.as_str(), if term == query { EXACT } else { PREFIX })),
+            );
+        } else if let Some((term, _)) = self.terms.get_key_value(query) {
+            matches.push((term.as_str(), EXACT));
+        }
+        let max = max_typos(query);
+        if max > 0 {
+            let query = query.chars().collect::<Vec<_>>();
+            for term in self.terms.keys() {
+                let chars = term.chars().collect::<Vec<_>>();
+                if let Some(typos @ 1..) = distance(&query, &chars, max) {
+                    matches.push((term, FUZZY[typos - 1]));
+                }
+            }
+        }
+        matches
+    }
+}
+
+impl SearchIndex {
+    /// Index a radio and its songs, replacing what was indexed for it before, only public
+    /// radios are found
+    pub fn index_radio(&self, id: &str, radio_state: &RadioState) {
+        let mut index = self.inner.write().unwrap();
+        index.remove_radio(id);
+        if radio_state.config.visibility != Visibility::Public {
+            return;
+        }
+        let doc = Doc::Radio(id.to_owned());
+        index.add(id, &doc, &html_text(&radio_state.config.title), 3.0);
+        index.add(id, &doc, id, 2.0);
+        index.add(id, &doc, &html_text(&radio_state.config.description), 1.0);
+        for tag in &radio_state.config.tags {
+            index.add(id, &doc, tag, 2.0);
+        }
+        index
+            .tags
+            .insert(id.to_owned(), radio_state.config.tags.clone());
+        for (name, &song) in &radio_state.song_map {
+            let doc = Doc::Song(id.to_owned(), song);
+            // The extension would match all songs of a format
+            let stem = name
+                .rsplit_once('.')
+                .map_or(name.as_str(), |(stem, _)| stem);
+            index.add(id, &doc, stem, 1.0);
+            let Some(meta) = radio_state.song_meta.get(&song) else {
+                continue;
+            };
+            for (field, weight) in [(&meta.title, 3.0), (&meta.artist, 2.0), (&meta.album, 1.0)] {
+                if let Some(field) = field {
+                    index.add(id, &doc, field, weight);
+                }
+            }
+        }
+    }
+
+    pub fn remove_radio(&self, id: &str) {
+        self.inner.write().unwrap().remove_radio(id);
+    }
+
+    /// Documents matching all words of `query` (case-insensitive, accent-folded, by prefix
+    /// or with typos), best first, only of radios with `tag` if given
+    pub fn search(&self, query: &str, tag: Option<&str>) -> Vec<(Doc, f32)> {
+        let index = self.inner.read().unwrap();
+        let mut scores: Option<HashMap<&Doc, f32>> = None;
+        for term in tokenize(query) {
+            let mut best = HashMap::<&Doc, f32>::new();
+            for (term, quality) in index.matches(&term) {
+                for (doc, weight) in &index.terms[term] {
+                    let score = best.entry(doc).or_default();
+                    *score = score.max(quality * weight);
+                }
+            }
+            scores = Some(match scores {
+                None => best,
+                Some(scores) => scores
+                    .into_iter()
+                    .filter_map(|(doc, score)| Some((doc, score + best.get(doc)?)))
+                    .collect(),
+            });
+        }
+        let mut results = scores
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|(doc, _)| {
+                tag.is_none_or(|tag| {
+                    index
+                        .tags
+                        .get(doc.radio())
+                        .is_some_and(|tags| tags.iter().any(|t| t == tag))
+                })
+            })
+            .map(|(doc, score)| (doc.clone(), score))
+            .collect::<Vec<_>>();
+        results.sort_by(|(a_doc, a), (b_doc, b)| b.total_cmp(a).then_with(|| a_doc.cmp(b_doc)));
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, path::PathBuf};
+
+    use openidconnect::SubjectIdentifier;
+    use tokio::sync::watch;
+
+    use super::*;
+    use crate::{
+        events::RadioEvents, listeners::Listeners, metadata::SongMeta, recordings::Recorder,
+        timeshift::Archive, Config,
+    };
+
+    fn radio(visibility: Visibility, title: &str, songs: &[(&str, Option<&str>)]) -> RadioState {
+        let events = RadioEvents::default();
+        RadioState {
+            config: Config {
+                title: title.into(),
+                description: "<p>Music &amp; talk</p>".into(),
+                tags: vec!["jazz".to_owned()],
+                visibility,
+            },
+            stream: watch::channel(Default::default()).1,
+            song_map: songs
+                .iter()
+                .zip(0..)
+                .map(|((name, _), song)| (name.to_string(), song))
+                .collect(),
+            song_meta: songs
+                .iter()
+                .zip(0..)
+                .map(|((_, artist), song)| {
+                    let meta = SongMeta {
+                        artist: artist.map(str::to_owned),
+                        ..Default::default()
+                    };
+                    (song, meta)
+                })
+                .collect(),
+            next_song_id: songs.len() as SongId,
+            song_order: songs.iter().map(|(name, _)| name.to_string()).collect(),
+            owner: SubjectIdentifier::new("owner".to_owned()),
+            members: HashMap::new(),
+            listeners: Listeners::new(events.clone()),
+            events,
+            archive: Archive::new(PathBuf::new(), 0),
+            recorder: Recorder::new(None),
+            relay: None,
+            shares: vec![],
+        }
+    }
+
+    fn docs(results: Vec<(Doc, f32)>) -> Vec<Doc> {
+        results.into_iter().map(|(doc, _)| doc).collect()
+    }
+
+    #[test]
+    fn tokenizes() {
+        assert_eq!(
+            tokenize("Björk – Jóga (Live, 1997)"),
+            ["bjork", "joga", "live", "1997"]
+        );
+        assert_eq!(
+            tokenize("Straße ÆON Œuvre Ørsted Łódź Đorđe"),
+            ["strasse", "aeon", "oeuvre", "orsted", "lodz", "dorde"]
+        );
+        assert_eq!(tokenize("ﬁne_tune's"), ["fine", "tune", "s"]);
+        assert!(tokenize(" -- ").is_empty());
+    }
+
+    #[test]
+    fn strips_markup() {
+        assert_eq!(
+            tokenize(&html_text("<p>Rock&amp;<b>Roll</b></p>")),
+            ["rock", "roll"]
+        );
+    }
+
+    #[test]
+    fn measures_distance() {
+        let chars = |s: &str| s.chars().collect::<Vec<_>>();
+        let distance = |a, b, max| distance(&chars(a), &chars(b), max);
+        assert_eq!(distance("jazz", "jazz", 1), Some(0));
+        assert_eq!(distance("jazz", "jaz", 1), Some(1));
+        assert_eq!(distance("jazz", "jizz", 1), Some(1));
+        assert_eq!(distance("jazz", "ajzz", 2), Some(2));
+        assert_eq!(distance("kitten", "sitting", 3), Some(3));
+        assert_eq!(distance("kitten", "sitting", 2), None);
+        assert_eq!(distance("a", "abcd", 2), None);
+        assert_eq!(distance("", "ab", 2), Some(2));
+    }
+
+    #[test]
+    fn allows_typos_by_length() {
+        assert_eq!(max_typos("abc"), 0);
+        assert_eq!(max_typos("abcd"), 1);
+        assert_eq!(max_typos("abcdefg"), 1);
+        assert_eq!(max_typos("abcdefgh"), 2);
+        // Characters, not bytes
+        assert_eq!(max_typos("äöü"), 0);
+    }
+
+    #[test]
+    fn finds_by_prefix_and_typos_best_first() {
+        let index = SearchIndex::default();
+        index.index_radio(
+            "night",
+            &radio(
+                Visibility::Public,
+                "Night Jazz",
+                &[("Blue Train.mp3", Some("Coltrane")), ("Train.mp3", None)],
+            ),
+        );
+        assert_eq!(
+            docs(index.search("colt", None)),
+            [Doc::Song("night".to_owned(), 0)]
+        );
+        assert_eq!(
+            docs(index.search("TRAIN", None)),
+            [
+                Doc::Song("night".to_owned(), 0),
+                Doc::Song("night".to_owned(), 1),
+            ]
+        );
+        assert_eq!(
+            docs(index.search("nght jaz", None)),
+            [Doc::Radio("night".to_owned())]
+        );
+        assert_eq!(
+            docs(index.search("blue train", None)),
+            [Doc::Song("night".to_owned(), 0)]
+        );
+        // The extension isn't indexed
+        assert!(index.search("mp3", None).is_empty());
+        assert!(index.search("", None).is_empty());
+        assert_eq!(index.search("talk", Some("jazz")).len(), 1);
+        assert!(index.search("talk", Some("rock")).is_empty());
+    }
+
+    #[test]
+    fn finds_only_public_radios() {
+        let index = SearchIndex::default();
+        let songs = [("Jazz Song.mp3", Some("Jazz Artist"))];
+        for (id, visibility) in [
+            ("public", Visibility::Public),
+            ("unlisted", Visibility::Unlisted),
+            ("private", Visibility::Private),
+        ] {
+            index.index_radio(id, &radio(visibility, &format!("{id} jazz"), &songs));
+        }
+        for query in ["jazz", "unlisted", "private", "song", "artist", "talk"] {
+            assert!(
+                index
+                    .search(query, None)
+                    .iter()
+                    .all(|(doc, _)| doc.radio() == "public"),
+                "{query}"
+            );
+        }
+        assert_eq!(index.search("jazz", None).len(), 2);
+
+        // Made private after being indexed as public
+        index.index_radio("public", &radio(Visibility::Private, "public jazz", &songs));
+        assert!(index.search("jazz", None).is_empty());
+        index.index_radio("public", &radio(Visibility::Public, "public jazz", &songs));
+        index.remove_radio("public");
+        assert!(index.search("jazz", None).is_empty());
+        assert!(index.inner.read().unwrap().terms.is_empty());
+    }
+}
@@ -0,0 +1,90 @@
+use jsonwebtoken::{decode, encode};
+use serde::{Deserialize, Serialize};
+
+use crate::{auth::OidcClient, listeners::unix_now};
+
+/// Seconds a share link is valid if not given
+pub const DEFAULT_SHARE_LIFETIME: u64 = 30 * 24 * 60 * 60;
+pub const MAX_SHARE_LIFETIME: u64 = 365 * 24 * 60 * 60;
+/// Most share links a radio can have at once
+pub const MAX_SHARES: usize = 100;
+
+/// Who can find and listen to a radio
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Visibility {
+    /// Listed on the start page and in search
+    #[default]
+    Public,
+    /// Not listed, but anyone knowing its id can listen
+    Unlisted,
+    /// Only with a share token or for its owner
+    Private,
+}
+
+impl Visibility {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Public => "public",
+            Self::Unlisted => "unlisted",
+            Self::Private => "private",
+        }
+    }
+}
+
+/// A share link of a radio, valid until revoked or expired
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Share {
+    pub id: String,
+    /// Unix time
+    pub created: u64,
+    /// Unix time
+    pub expires: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ShareClaims {
+    radio: String,
+    share: String,
+    exp: u64,
+}
+
+impl Share {
+    /// A new share link valid for `lifetime` seconds
+    pub fn new(lifetime: u64) -> Self {
+        let created = unix_now();
+        Self {
+            id: format!("{:032x}", rand::random::<u128>()),
+            created,
+            expires: created + lifetime,
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires <= unix_now()
+    }
+
+    /// The signed token giving access to `radio`
+    pub fn token(&self, radio: &str, oidc_client: &OidcClient) -> Option<String> {
+        encode(
+            &oidc_client.header,
+            &ShareClaims {
+                radio: radio.to_owned(),
+                share: self.id.clone(),
+                exp: self.expires,
+            },
+            &oidc_client.encoding_key,
+        )
+        .ok()
+    }
+}
+
+/// Whether `token` was signed for `radio` and its share link isn't revoked
+pub fn is_valid(token: &str, radio: &str, shares: &[Share], oidc_client: &OidcClient) -> bool {
+    let Ok(claims) =
+        decode::<ShareClaims>(token, &oidc_client.decoding_key, &oidc_client.validation)
+    else {
+        return false;
+    };
+    claims.claims.radio == radio && shares.iter().any(|share| share.id == claims.claims.share)
+}
@@ -0,0 +1,765 @@
+use std::path::Path;
+
+use crate::{
+    blobs, recordings::recordings_key, storage::Storage, PersistentAppState, SongId,
+    RESERVED_RADIO_IDS, ROUTE_RADIO_IDS,
+};
+
+/// Marks a versioned state file, older files start directly with the state
+const MAGIC: &[u8; 4] = b"JARI";
+/// Version of the `PersistentAppState` layout
+const VERSION: u32 = 10;
+/// Version of the layout of the data directory
+const LAYOUT_VERSION: u32 = 2;
+
+/// Encode the app state for saving
+pub fn encode(state: &PersistentAppState) -> postcard::Result<Vec<u8>> {
+    let mut buf = MAGIC.to_vec();
+    buf.extend_from_slice(&VERSION.to_le_bytes());
+    postcard::to_extend(state, buf)
+}
+
+/// Decode a saved app state, migrating older layouts
+pub fn decode(buf: &[u8]) -> postcard::Result<PersistentAppState> {
+    let Some(versioned) = buf.strip_prefix(MAGIC) else {
+        return postcard::from_bytes::<v0::PersistentAppState>(buf)
+            .map(v1::PersistentAppState::from)
+            .map(v2::PersistentAppState::from)
+            .map(v3::PersistentAppState::from)
+            .map(v4::PersistentAppState::from)
+            .map(v5::PersistentAppState::from)
+            .map(v6::PersistentAppState::from)
+            .map(v7::PersistentAppState::from)
+            .map(v8::PersistentAppState::from)
+            .map(v9::PersistentAppState::from)
+            .map(Into::into);
+    };
+    let (version, state) = versioned.split_at(4.min(versioned.len()));
+    match version.try_into().map(u32::from_le_bytes) {
+        Ok(1) => postcard::from_bytes::<v1::PersistentAppState>(state)
+            .map(v2::PersistentAppState::from)
+            .map(v3::PersistentAppState::from)
+            .map(v4::PersistentAppState::from)
+            .map(v5::PersistentAppState::from)
+            .map(v6::PersistentAppState::from)
+            .map(v7::PersistentAppState::from)
+            .map(v8::PersistentAppState::from)
+            .map(v9::PersistentAppState::from)
+            .map(Into::into),
+        Ok(2) => postcard::from_bytes::<v2::PersistentAppState>(state)
+            .map(v3::PersistentAppState::from)
+            .map(v4::PersistentAppState::from)
+            .map(v5::PersistentAppState::from)
+            .map(v6::PersistentAppState::from)
+            .map(v7::PersistentAppState::from)
+            .map(v8::PersistentAppState::from)
+            .map(v9::PersistentAppState::from)
+            .map(Into::into),
+        Ok(3) => postcard::from_bytes::<v3::PersistentAppState>(state)
+            .map(v4::PersistentAppState::from)
+            .map(v5::PersistentAppState::from)
+            .map(v6::PersistentAppState::from)
+            .map(v7::PersistentAppState::from)
+            .map(v8::PersistentAppState::from)
+            .map(v9::PersistentAppState::from)
+            .map(Into::into),
+        Ok(4) => postcard::from_bytes::<v4::PersistentAppState>(state)
+            .map(v5::PersistentAppState::from)
+            .map(v6::PersistentAppState::from)
+            .map(v7::PersistentAppState::from)
+            .map(v8::PersistentAppState::from)
+            .map(v9::PersistentAppState::from)
+            .map(Into::into),
+        Ok(5) => postcard::from_bytes::<v5::PersistentAppState>(state)
+            .map(v6::PersistentAppState::from)
+            .map(v7::PersistentAppState::from)
+            .map(v8::PersistentAppState::from)
+            .map(v9::PersistentAppState::from)
+            .map(Into::into),
+        Ok(6) => postcard::from_bytes::<v6::PersistentAppState>(state)
+            .map(v7::PersistentAppState::from)
+            .map(v8::PersistentAppState::from)
+            .map(v9::PersistentAppState::from)
+            .map(Into::into),
+        Ok(7) => postcard::from_bytes::<v7::PersistentAppState>(state)
+            .map(v8::PersistentAppState::from)
+            .map(v9::PersistentAppState::from)
+            .map(Into::into),
+        Ok(8) => postcard::from_bytes::<v8::PersistentAppState>(state)
+            .map(v9::PersistentAppState::from)
+            .map(Into::into),
+        Ok(9) => postcard::from_bytes::<v9::PersistentAppState>(state).map(Into::into),
+        Ok(VERSION) => postcard::from_bytes(state),
+        _ => Err(postcard::Error::DeserializeBadEncoding),
+    }
+}
+
+/// Bring the data directory up to the current layout
+pub async fn migrate_layout(
+    data_dir: &Path,
+    state: &mut PersistentAppState,
+) -> std::io::Result<()> {
+    tokio::fs::create_dir_all(data_dir).await?;
+    let layout_file = data_dir.join("layout");
+    let layout = match tokio::fs::read_to_string(&layout_file).await {
+        Ok(layout) => layout.trim().parse().unwrap_or(0),
+        Err(_) => 0,
+    };
+    if layout >= LAYOUT_VERSION {
+        return Ok(());
+    }
+    if layout < 1 {
+        // 0 -> 1: song directories stay named by id, but ids were reused, so new ones
+        // must not collide with directories of songs lost from the state
+        for (name, radio_state) in state.radio_states.iter_mut() {
+            let Ok(mut dir) = tokio::fs::read_dir(data_dir.join(name)).await else {
+                continue;
+            };
+            while let Some(entry) = dir.next_entry().await? {
+                if let Some(id) = entry
+                    .file_name()
+                    .to_str()
+                    .and_then(|id| id.parse::<SongId>().ok())
+                {
+                    radio_state.next_song_id = radio_state.next_song_id.max(id.saturating_add(1));
+                }
+            }
+        }
+    }
+    if layout < 2 {
+        // 1 -> 2: segments move from the song directories into shared blobs
+        let root = data_dir.to_owned();
+        let radios = state.radio_states.keys().cloned().collect::<Vec<_>>();
+        tokio::task::spawn_blocking(move || {
+            std::fs::create_dir_all(root.join(blobs::BLOBS_DIR))?;
+            for radio in radios {
+                let Ok(dir) = std::fs::read_dir(root.join(radio)) else {
+                    continue;
+                };
+                for song in dir {
+                    let song = song?;
+                    if song
+                        .file_name()
+                        .to_str()
+                        .and_then(|id| id.parse::<SongId>().ok())
+                        .is_some()
+                    {
+                        blobs::migrate_song_dir(&root, &song.path())?;
+                    }
+                }
+            }
+            Ok::<_, std::io::Error>(())
+        })
+        .await??;
+    }
+    tokio::fs::write(layout_file, LAYOUT_VERSION.to_string()).await
+}
+
+/// Give radios created before their ids were reserved a new id, moving their data along
+///
+/// Must run before anything below the data directory is cleaned up. Only the keys of
+/// the radio's songs and recordings are moved, as the reserved ids share their prefix
+/// with other data. Keys are copied to the new id before the old ones are removed, so
+/// this can be repeated if interrupted. Share links of such radios stop working.
+pub fn rename_reserved(
+    storage: &dyn Storage,
+    state: &mut PersistentAppState,
+) -> std::io::Result<()> {
+    for id in RESERVED_RADIO_IDS.into_iter().chain(ROUTE_RADIO_IDS) {
+        let Some(radio_state) = state.radio_states.remove(id) else {
+            continue;
+        };
+        let new_id = (1..)
+            .map(|i| match i {
+                1 => format!("{id}-radio"),
+                i => format!("{id}-radio-{i}"),
+            })
+            .find(|new_id| !state.radio_states.contains_key(new_id))
+            .unwrap();
+        eprintln!("Radio id {id} is reserved, renaming the radio to {new_id}");
+        let prefixes = radio_state
+            .song_map
+            .values()
+            .map(|song| format!("{id}/{song}"))
+            .chain([recordings_key(id)]);
+        for prefix in prefixes {
+            let keys = storage.list(&prefix)?;
+            for key in &keys {
+                let new_key = format!("{new_id}{}", &key[id.len()..]);
+                storage.write(&new_key, &storage.read(key)?)?;
+            }
+            if !keys.is_empty() {
+                storage.remove_prefix(&prefix)?;
+            }
+        }
+        for user in state.users.values_mut() {
+            for radio in user.radios.iter_mut().chain(&mut user.favorites) {
+                if radio == id {
+                    *radio = new_id.clone();
+                }
+            }
+        }
+        state.radio_states.insert(new_id, radio_state);
+    }
+    Ok(())
+}
+
+/// Users without global roles
+mod v9 {
+    use openidconnect::SubjectIdentifier;
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    use crate::auth::GlobalRole;
+
+    #[derive(Deserialize)]
+    pub struct User {
+        pub radios: Vec<String>,
+        pub favorites: Vec<String>,
+    }
+    #[derive(Deserialize)]
+    pub struct PersistentAppState {
+        pub radio_states: HashMap<String, crate::PersistentRadioState>,
+        pub users: HashMap<SubjectIdentifier, User>,
+    }
+
+    impl From<PersistentAppState> for crate::PersistentAppState {
+        fn from(state: PersistentAppState) -> Self {
+            Self {
+                radio_states: state.radio_states,
+                users: state
+                    .users
+                    .into_iter()
+                    .map(|(sub, user)| {
+                        // The sub ADMIN was allowed everything before there were roles
+                        let role = match sub.as_str() {
+                            "ADMIN" => GlobalRole::Admin,
+                            _ => GlobalRole::User,
+                        };
+                        let user = crate::User {
+                            radios: user.radios,
+                            favorites: user.favorites,
+                            role,
+                        };
+                        (sub, user)
+                    })
+                    .collect(),
+            }
+        }
+    }
+}
+
+/// Radios without members
+mod v8 {
+    use openidconnect::SubjectIdentifier;
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    use super::v9::User;
+    use crate::{
+        metadata::SongMeta, recordings::RecordingSchedule, sharing::Share, SentConfig, SongId,
+    };
+
+    #[derive(Deserialize)]
+    pub struct PersistentRadioState {
+        pub config: SentConfig,
+        pub song_map: HashMap<String, SongId>,
+        pub song_meta: HashMap<SongId, SongMeta>,
+        pub next_song_id: SongId,
+        pub song_order: Vec<String>,
+        pub owner: SubjectIdentifier,
+        pub recording_schedule: Option<RecordingSchedule>,
+        pub relay: Option<String>,
+        pub shares: Vec<Share>,
+    }
+    #[derive(Deserialize)]
+    pub struct PersistentAppState {
+        pub radio_states: HashMap<String, PersistentRadioState>,
+        pub users: HashMap<SubjectIdentifier, User>,
+    }
+
+    impl From<PersistentRadioState> for crate::PersistentRadioState {
+        fn from(state: PersistentRadioState) -> Self {
+            Self {
+                config: state.config,
+                song_map: state.song_map,
+                song_meta: state.song_meta,
+                next_song_id: state.next_song_id,
+                song_order: state.song_order,
+                owner: state.owner,
+                recording_schedule: state.recording_schedule,
+                relay: state.relay,
+                shares: state.shares,
+                members: HashMap::new(),
+            }
+        }
+    }
+    impl From<PersistentAppState> for super::v9::PersistentAppState {
+        fn from(state: PersistentAppState) -> Self {
+            Self {
+                radio_states: state
+                    .radio_states
+                    .into_iter()
+                    .map(|(name, radio)| (name, radio.into()))
+                    .collect(),
+                users: state.users,
+            }
+        }
+    }
+}
+
+/// Radios without visibility or share links
+mod v7 {
+    use openidconnect::SubjectIdentifier;
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    use super::v9::User;
+    use crate::{metadata::SongMeta, recordings::RecordingSchedule, SongId};
+
+    #[derive(Deserialize)]
+    pub struct SentConfig {
+        pub title: String,
+        pub description: String,
+        pub tags: Vec<String>,
+    }
+    #[derive(Deserialize)]
+    pub struct PersistentRadioState {
+        pub config: SentConfig,
+        pub song_map: HashMap<String, SongId>,
+        pub song_meta: HashMap<SongId, SongMeta>,
+        pub next_song_id: SongId,
+        pub song_order: Vec<String>,
+        pub owner: SubjectIdentifier,
+        pub recording_schedule: Option<RecordingSchedule>,
+        pub relay: Option<String>,
+    }
+    #[derive(Deserialize)]
+    pub struct PersistentAppState {
+        pub radio_states: HashMap<String, PersistentRadioState>,
+        pub users: HashMap<SubjectIdentifier, User>,
+    }
+
+    impl From<PersistentRadioState> for super::v8::PersistentRadioState {
+        fn from(state: PersistentRadioState) -> Self {
+            Self {
+                config: crate::SentConfig {
+                    title: state.config.title,
+                    description: state.config.description,
+                    tags: state.config.tags,
+                    visibility: Default::default(),
+                },
+                song_map: state.song_map,
+                song_meta: state.song_meta,
+                next_song_id: state.next_song_id,
+                song_order: state.song_order,
+                owner: state.owner,
+                recording_schedule: state.recording_schedule,
+                relay: state.relay,
+                shares: vec![],
+            }
+        }
+    }
+    impl From<PersistentAppState> for super::v8::PersistentAppState {
+        fn from(state: PersistentAppState) -> Self {
+            Self {
+                radio_states: state
+                    .radio_states
+                    .into_iter()
+                    .map(|(name, radio)| (name, radio.into()))
+                    .collect(),
+                users: state.users,
+            }
+        }
+    }
+}
+
+/// Users without favorites
+mod v6 {
+    use openidconnect::SubjectIdentifier;
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    #[derive(Deserialize)]
+    pub struct PersistentAppState {
+        pub radio_states: HashMap<String, super::v7::PersistentRadioState>,
+        pub users: HashMap<SubjectIdentifier, Vec<String>>,
+    }
+
+    impl From<PersistentAppState> for super::v7::PersistentAppState {
+        fn from(state: PersistentAppState) -> Self {
+            Self {
+                radio_states: state.radio_states,
+                users: state
+                    .users
+                    .into_iter()
+                    .map(|(sub, radios)| {
+                        (
+                            sub,
+                            super::v9::User {
+                                radios,
+                                favorites: vec![],
+                            },
+                        )
+                    })
+                    .collect(),
+            }
+        }
+    }
+}
+
+/// Radios without tags
+mod v5 {
+    use openidconnect::SubjectIdentifier;
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    use super::v1::SentConfig;
+    use crate::{metadata::SongMeta, recordings::RecordingSchedule, SongId};
+
+    #[derive(Deserialize)]
+    pub struct PersistentRadioState {
+        pub config: SentConfig,
+        pub song_map: HashMap<String, SongId>,
+        pub song_meta: HashMap<SongId, SongMeta>,
+        pub next_song_id: SongId,
+        pub song_order: Vec<String>,
+        pub owner: SubjectIdentifier,
+        pub recording_schedule: Option<RecordingSchedule>,
+        pub relay: Option<String>,
+    }
+    #[derive(Deserialize)]
+    pub struct PersistentAppState {
+        pub radio_states: HashMap<String, PersistentRadioState>,
+        pub users: HashMap<SubjectIdentifier, Vec<String>>,
+    }
+
+    impl From<PersistentRadioState> for super::v7::PersistentRadioState {
+        fn from(state: PersistentRadioState) -> Self {
+            Self {
+                config: super::v7::SentConfig {
+                    title: state.config.title,
+                    description: state.config.description,
+                    tags: vec![],
+                },
+                song_map: state.song_map,
+                song_meta: state.song_meta,
+                next_song_id: state.next_song_id,
+                song_order: state.song_order,
+                owner: state.owner,
+                recording_schedule: state.recording_schedule,
+                relay: state.relay,
+            }
+        }
+    }
+    impl From<PersistentAppState> for super::v6::PersistentAppState {
+        fn from(state: PersistentAppState) -> Self {
+            Self {
+                radio_states: state
+                    .radio_states
+                    .into_iter()
+                    .map(|(name, radio)| (name, radio.into()))
+                    .collect(),
+                users: state.users,
+            }
+        }
+    }
+}
+
+/// Radios without a relay
+mod v4 {
+    use openidconnect::SubjectIdentifier;
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    use super::v1::SentConfig;
+    use crate::{metadata::SongMeta, recordings::RecordingSchedule, SongId};
+
+    #[derive(Deserialize)]
+    pub struct PersistentRadioState {
+        pub config: SentConfig,
+        pub song_map: HashMap<String, SongId>,
+        pub song_meta: HashMap<SongId, SongMeta>,
+        pub next_song_id: SongId,
+        pub song_order: Vec<String>,
+        pub owner: SubjectIdentifier,
+        pub recording_schedule: Option<RecordingSchedule>,
+    }
+    #[derive(Deserialize)]
+    pub struct PersistentAppState {
+        pub radio_states: HashMap<String, PersistentRadioState>,
+        pub users: HashMap<SubjectIdentifier, Vec<String>>,
+    }
+
+    impl From<PersistentRadioState> for super::v5::PersistentRadioState {
+        fn from(state: PersistentRadioState) -> Self {
+            Self {
+                config: state.config,
+                song_map: state.song_map,
+                song_meta: state.song_meta,
+                next_song_id: state.next_song_id,
+                song_order: state.song_order,
+                owner: state.owner,
+                recording_schedule: state.recording_schedule,
+                relay: None,
+            }
+        }
+    }
+    impl From<PersistentAppState> for super::v5::PersistentAppState {
+        fn from(state: PersistentAppState) -> Self {
+            Self {
+                radio_states: state
+                    .radio_states
+                    .into_iter()
+                    .map(|(name, radio)| (name, radio.into()))
+                    .collect(),
+                users: state.users,
+            }
+        }
+    }
+}
+
+/// Radios without a recording schedule
+mod v3 {
+    use openidconnect::SubjectIdentifier;
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    use super::v1::SentConfig;
+    use crate::{metadata::SongMeta, SongId};
+
+    #[derive(Deserialize)]
+    pub struct PersistentRadioState {
+        pub config: SentConfig,
+        pub song_map: HashMap<String, SongId>,
+        pub song_meta: HashMap<SongId, SongMeta>,
+        pub next_song_id: SongId,
+        pub song_order: Vec<String>,
+        pub owner: SubjectIdentifier,
+    }
+    #[derive(Deserialize)]
+    pub struct PersistentAppState {
+        pub radio_states: HashMap<String, PersistentRadioState>,
+        pub users: HashMap<SubjectIdentifier, Vec<String>>,
+    }
+
+    impl From<PersistentRadioState> for super::v4::PersistentRadioState {
+        fn from(state: PersistentRadioState) -> Self {
+            Self {
+                config: state.config,
+                song_map: state.song_map,
+                song_meta: state.song_meta,
+                next_song_id: state.next_song_id,
+                song_order: state.song_order,
+                owner: state.owner,
+                recording_schedule: None,
+            }
+        }
+    }
+    impl From<PersistentAppState> for super::v4::PersistentAppState {
+        fn from(state: PersistentAppState) -> Self {
+            Self {
+                radio_states: state
+                    .radio_states
+                    .into_iter()
+                    .map(|(name, radio)| (name, radio.into()))
+                    .collect(),
+                users: state.users,
+            }
+        }
+    }
+}
+
+/// Song metadata without the upload size
+mod v2 {
+    use openidconnect::SubjectIdentifier;
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    use super::v1::SentConfig;
+    use crate::SongId;
+
+    #[derive(Default, Deserialize)]
+    pub struct SongMeta {
+        pub title: Option<String>,
+        pub artist: Option<String>,
+        pub album: Option<String>,
+        pub track: Option<u32>,
+        pub year: Option<i32>,
+        pub genre: Option<String>,
+        pub duration: Option<f64>,
+    }
+    #[derive(Deserialize)]
+    pub struct PersistentRadioState {
+        pub config: SentConfig,
+        pub song_map: HashMap<String, SongId>,
+        pub song_meta: HashMap<SongId, SongMeta>,
+        pub next_song_id: SongId,
+        pub song_order: Vec<String>,
+        pub owner: SubjectIdentifier,
+    }
+    #[derive(Deserialize)]
+    pub struct PersistentAppState {
+        pub radio_states: HashMap<String, PersistentRadioState>,
+        pub users: HashMap<SubjectIdentifier, Vec<String>>,
+    }
+
+    impl From<SongMeta> for crate::metadata::SongMeta {
+        fn from(meta: SongMeta) -> Self {
+            Self {
+                title: meta.title,
+                artist: meta.artist,
+                album: meta.album,
+                track: meta.track,
+                year: meta.year,
+                genre: meta.genre,
+                duration: meta.duration,
+                size: None,
+            }
+        }
+    }
+    impl From<PersistentRadioState> for super::v3::PersistentRadioState {
+        fn from(state: PersistentRadioState) -> Self {
+            Self {
+                config: state.config,
+                song_map: state.song_map,
+                song_meta: state
+                    .song_meta
+                    .into_iter()
+                    .map(|(id, meta)| (id, meta.into()))
+                    .collect(),
+                next_song_id: state.next_song_id,
+                song_order: state.song_order,
+                owner: state.owner,
+            }
+        }
+    }
+    impl From<PersistentAppState> for super::v3::PersistentAppState {
+        fn from(state: PersistentAppState) -> Self {
+            Self {
+                radio_states: state
+                    .radio_states
+                    .into_iter()
+                    .map(|(name, radio)| (name, radio.into()))
+                    .collect(),
+                users: state.users,
+            }
+        }
+    }
+}
+
+/// Song ids were `u8` and reused
+mod v1 {
+    use openidconnect::SubjectIdentifier;
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    use super::v2::SongMeta;
+    use crate::SongId;
+
+    #[derive(Deserialize)]
+    pub struct SentConfig {
+        pub title: String,
+        pub description: String,
+    }
+    #[derive(Deserialize)]
+    pub struct PersistentRadioState {
+        pub config: SentConfig,
+        pub song_map: HashMap<String, u8>,
+        pub song_meta: HashMap<u8, SongMeta>,
+        pub song_order: Vec<String>,
+        pub owner: SubjectIdentifier,
+    }
+    #[derive(Deserialize)]
+    pub struct PersistentAppState {
+        pub radio_states: HashMap<String, PersistentRadioState>,
+        pub users: HashMap<SubjectIdentifier, Vec<String>>,
+    }
+
+    impl From<PersistentRadioState> for super::v2::PersistentRadioState {
+        fn from(state: PersistentRadioState) -> Self {
+            Self {
+                config: state.config,
+                next_song_id: state
+                    .song_map
+                    .values()
+                    .max()
+                    .map_or(0, |&id| SongId::from(id) + 1),
+                song_map: state
+                    .song_map
+                    .into_iter()
+                    .map(|(name, id)| (name, id.into()))
+                    .collect(),
+                song_meta: state
+                    .song_meta
+                    .into_iter()
+                    .map(|(id, meta)| (id.into(), meta))
+                    .collect(),
+                song_order: state.song_order,
+                owner: state.owner,
+            }
+        }
+    }
+    impl From<PersistentAppState> for super::v2::PersistentAppState {
+        fn from(state: PersistentAppState) -> Self {
+            Self {
+                radio_states: state
+                    .radio_states
+                    .into_iter()
+                    .map(|(name, radio)| (name, radio.into()))
+                    .collect(),
+                users: state.users,
+            }
+        }
+    }
+}
+
+/// The unversioned layout, without song metadata
+mod v0 {
+    use openidconnect::SubjectIdentifier;
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    use super::v1::SentConfig;
+
+    #[derive(Deserialize)]
+    pub struct PersistentRadioState {
+        config: SentConfig,
+        song_map: HashMap<String, u8>,
+        song_order: Vec<String>,
+        owner: SubjectIdentifier,
+    }
+    #[derive(Deserialize)]
+    pub struct PersistentAppState {
+        radio_states: HashMap<String, PersistentRadioState>,
+        users: HashMap<SubjectIdentifier, Vec<String>>,
+    }
+
+    impl From<PersistentRadioState> for super::v1::PersistentRadioState {
+        fn from(state: PersistentRadioState) -> Self {
+            Self {
+                config: state.config,
+                song_meta: state
+                    .song_map
+                    .values()
+                    .map(|&id| (id, Default::default()))
+                    .collect(),
+                song_map: state.song_map,
+                song_order: state.song_order,
+                owner: state.owner,
+            }
+        }
+    }
+    impl From<PersistentAppState> for super::v1::PersistentAppState {
+        fn from(state: PersistentAppState) -> Self {
+            Self {
+                radio_states: state
+                    .radio_states
+                    .into_iter()
+                    .map(|(name, radio)| (name, radio.into()))
+                    .collect(),
+                users: state.users,
+            }
+        }
+    }
+}
@@ -0,0 +1,241 @@
+use std::{
+    fmt::Debug,
+    fs::{create_dir_all, read, read_dir, remove_dir_all, remove_file, write},
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+use s3::{creds::Credentials, error::S3Error, Bucket, Region};
+
+/// Where songs (segments, `len`, covers, blob references) are kept
+///
+/// Keys are `/` separated paths relative to the data directory, like
+/// `blobs/{hash}/0.aac` or `{radio}/{song}/cover`.
+pub trait Storage: Debug + Send + Sync {
+    fn read(&self, key: &str) -> io::Result<Vec<u8>>;
+    fn write(&self, key: &str, data: &[u8]) -> io::Result<()>;
+    /// Size in bytes of the data of a key
+    fn size(&self, key: &str) -> io::Result<u64>;
+    /// Remove a key, succeeds if it doesn't exist
+    fn remove(&self, key: &str) -> io::Result<()>;
+    /// Remove all keys below `prefix/`
+    fn remove_prefix(&self, prefix: &str) -> io::Result<()>;
+    /// All keys below `prefix/` (recursively), all keys for the empty prefix
+    fn list(&self, prefix: &str) -> io::Result<Vec<String>>;
+    /// Names of the keys and prefixes directly below `prefix/`, without what's below them
+    fn list_names(&self, prefix: &str) -> io::Result<Vec<String>>;
+
+    fn read_to_string(&self, key: &str) -> io::Result<String> {
+        String::from_utf8(self.read(key)?).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))
+    }
+}
+
+/// Pick the storage from the environment, S3 if `S3_BUCKET` is set, the data directory otherwise
+pub fn from_env(data_dir: PathBuf) -> Box<dyn Storage> {
+    dotenvy::dotenv().ok();
+    match std::env::var("S3_BUCKET") {
+        Ok(bucket) => Box::new(S3Storage::from_env(&bucket).expect("Invalid S3 configuration")),
+        Err(_) => Box::new(LocalStorage::new(data_dir)),
+    }
+}
+
+/// Files below the data directory
+#[derive(Debug)]
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+}
+
+fn ignore_not_found(res: io::Result<()>) -> io::Result<()> {
+    match res {
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        res => res,
+    }
+}
+
+/// Key of `name` below `prefix`, the empty prefix being the root
+fn sub_key(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_owned()
+    } else {
+        format!("{prefix}/{name}")
+    }
+}
+
+fn list_dir(dir: &Path, prefix: &str, keys: &mut Vec<String>) -> io::Result<()> {
+    for entry in read_dir(dir)? {
+        let entry = entry?;
+        let Some(name) = entry.file_name().to_str().map(|name| sub_key(prefix, name)) else {
+            continue;
+        };
+        if entry.file_type()?.is_dir() {
+            list_dir(&entry.path(), &name, keys)?;
+        } else {
+            keys.push(name);
+        }
+    }
+    Ok(())
+}
+
+impl Storage for LocalStorage {
+    fn read(&self, key: &str) -> io::Result<Vec<u8>> {
+        read(self.root.join(key))
+    }
+
+    fn write(&self, key: &str, data: &[u8]) -> io::Result<()> {
+        let path = self.root.join(key);
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent)?;
+        }
+        write(path, data)
+    }
+
+    fn size(&self, key: &str) -> io::Result<u64> {
+        Ok(std::fs::metadata(self.root.join(key))?.len())
+    }
+
+    fn remove(&self, key: &str) -> io::Result<()> {
+        ignore_not_found(remove_file(self.root.join(key)))
+    }
+
+    fn remove_prefix(&self, prefix: &str) -> io::Result<()> {
+        ignore_not_found(remove_dir_all(self.root.join(prefix)))
+    }
+
+    fn list(&self, prefix: &str) -> io::Result<Vec<String>> {
+        let mut keys = vec![];
+        match list_dir(&self.root.join(prefix), prefix, &mut keys) {
+            // Nothing is below a file either
+            Err(e) if matches!(e.kind(), ErrorKind::NotFound | ErrorKind::NotADirectory) => {
+                Ok(vec![])
+            }
+            res => res.map(|()| keys),
+        }
+    }
+
+    fn list_names(&self, prefix: &str) -> io::Result<Vec<String>> {
+        let dir = match read_dir(self.root.join(prefix)) {
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+            dir => dir?,
+        };
+        let mut names = vec![];
+        for entry in dir {
+            if let Some(name) = entry?.file_name().to_str() {
+                names.push(name.to_owned());
+            }
+        }
+        Ok(names)
+    }
+}
+
+/// Objects in a bucket of an S3 compatible service (like MinIO)
+#[derive(Debug)]
+pub struct S3Storage {
+    bucket: Box<Bucket>,
+}
+
+fn s3_error(e: S3Error) -> io::Error {
+    match e {
+        S3Error::HttpFailWithBody(404, _) => io::Error::from(ErrorKind::NotFound),
+        e => io::Error::other(e),
+    }
+}
+
+impl S3Storage {
+    /// Configure from `S3_ENDPOINT`, `S3_REGION`, `S3_ACCESS_KEY` and `S3_SECRET_KEY`
+    ///
+    /// With a custom endpoint, path style addressing is used as MinIO expects.
+    pub fn from_env(bucket: &str) -> Result<Self, S3Error> {
+        let region = std::env::var("S3_REGION").unwrap_or("us-east-1".to_owned());
+        let region = match std::env::var("S3_ENDPOINT") {
+            Ok(endpoint) => Region::Custom { region, endpoint },
+            Err(_) => region.parse()?,
+        };
+        let credentials = Credentials::new(
+            std::env::var("S3_ACCESS_KEY").ok().as_deref(),
+            std::env::var("S3_SECRET_KEY").ok().as_deref(),
+            None,
+            None,
+            None,
+        )?;
+        let custom = matches!(region, Region::Custom { .. });
+        let mut bucket = Bucket::new(bucket, region, credentials)?;
+        if custom {
+            bucket = bucket.with_path_style();
+        }
+        Ok(Self { bucket })
+    }
+}
+
+impl Storage for S3Storage {
+    fn read(&self, key: &str) -> io::Result<Vec<u8>> {
+        self.bucket
+            .get_object(key)
+            .map(|res| res.to_vec())
+            .map_err(s3_error)
+    }
+
+    fn write(&self, key: &str, data: &[u8]) -> io::Result<()> {
+        self.bucket
+            .put_object(key, data)
+            .map(|_| ())
+            .map_err(s3_error)
+    }
+
+    fn size(&self, key: &str) -> io::Result<u64> {
+        let (head, status) = self.bucket.head_object(key).map_err(s3_error)?;
+        if status == 404 {
+            return Err(ErrorKind::NotFound.into());
+        }
+        head.content_length
+            .and_then(|len| len.try_into().ok())
+            .ok_or_else(|| io::Error::other("Missing content length"))
+    }
+
+    fn remove(&self, key: &str) -> io::Result<()> {
+        ignore_not_found(self.bucket.delete_object(key).map(|_| ()).map_err(s3_error))
+    }
+
+    fn remove_prefix(&self, prefix: &str) -> io::Result<()> {
+        for key in self.list(prefix)? {
+            self.remove(&key)?;
+        }
+        Ok(())
+    }
+
+    fn list(&self, prefix: &str) -> io::Result<Vec<String>> {
+        Ok(self
+            .bucket
+            .list(sub_key(prefix, ""), None)
+            .map_err(s3_error)?
+            .into_iter()
+            .flat_map(|page| page.contents)
+            .map(|object| object.key)
+            .collect())
+    }
+
+    fn list_names(&self, prefix: &str) -> io::Result<Vec<String>> {
+        let prefix = sub_key(prefix, "");
+        Ok(self
+            .bucket
+            .list(prefix.clone(), Some("/".to_owned()))
+            .map_err(s3_error)?
+            .into_iter()
+            .flat_map(|page| {
+                page.contents
+                    .into_iter()
+                    .map(|object| object.key)
+                    .chain(page.common_prefixes.into_iter().flatten().map(|p| p.prefix))
+            })
+            .filter_map(|key| {
+                key.strip_prefix(&prefix)
+                    .map(|name| name.trim_end_matches('/').to_owned())
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,693 @@
+use std::{collections::HashMap, fmt, mem::take};
+
+use crate::CleanString;
+
+/// Data a template is rendered with
+#[derive(Debug, Clone)]
+pub enum Value {
+    /// Plain text, escaped for where it is inserted
+    Text(String),
+    /// Markup already cleaned for element content (like a `CleanString`)
+    Html(String),
+    Bool(bool),
+    List(Vec<Value>),
+    Map(HashMap<String, Value>),
+}
+
+impl Value {
+    pub fn map<'a>(entries: impl IntoIterator<Item = (&'a str, Value)>) -> Self {
+        Self::Map(
+            entries
+                .into_iter()
+                .map(|(key, value)| (key.to_owned(), value))
+                .collect(),
+        )
+    }
+
+    /// Whether `{% if %}` takes the value as true
+    fn is_truthy(&self) -> bool {
+        match self {
+            Self::Text(text) | Self::Html(text) => !text.is_empty(),
+            Self::Bool(value) => *value,
+            Self::List(items) => !items.is_empty(),
+            Self::Map(entries) => !entries.is_empty(),
+        }
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Self::Text(value)
+    }
+}
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Self::Text(value.to_owned())
+    }
+}
+impl From<usize> for Value {
+    fn from(value: usize) -> Self {
+        Self::Text(value.to_string())
+    }
+}
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+impl From<&CleanString> for Value {
+    fn from(value: &CleanString) -> Self {
+        Self::Html(value.to_string())
+    }
+}
+impl From<Vec<Value>> for Value {
+    fn from(value: Vec<Value>) -> Self {
+        Self::List(value)
+    }
+}
+
+/// A template that can't be parsed
+#[derive(Debug, Clone)]
+pub struct TemplateError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+/// How a value is escaped, from where it is in the page
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Escape {
+    /// Element content
+    Text,
+    /// A quoted attribute value
+    Attribute,
+    /// A string literal in a script or event handler attribute
+    Script,
+}
+
+#[derive(Debug)]
+enum Node {
+    Text(String),
+    Value {
+        path: Vec<String>,
+        escape: Escape,
+    },
+    For {
+        var: String,
+        path: Vec<String>,
+        body: Vec<Node>,
+    },
+    If {
+        negate: bool,
+        path: Vec<String>,
+        then: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+}
+
+/// Where the scanner is in the HTML
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    Text,
+    Comment,
+    /// Inside a tag, outside attribute values
+    Tag,
+    /// After the `=` of an attribute
+    BeforeValue,
+    Attribute {
+        quote: u8,
+    },
+    Unquoted,
+    Script,
+    Style,
+}
+
+/// Follows the HTML context through the literal parts of a template
+#[derive(Debug, Clone)]
+struct Scanner {
+    state: State,
+    /// Name of the current tag, lowercase
+    tag: String,
+    closing: bool,
+    naming_tag: bool,
+    /// Name of the current attribute, lowercase
+    attr: String,
+    attr_ended: bool,
+    /// The text so far ends with a `<`, that a value would turn into a tag
+    after_lt: bool,
+}
+
+fn starts_with_ignore_case(bytes: &[u8], prefix: &[u8]) -> bool {
+    bytes
+        .get(..prefix.len())
+        .is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+}
+
+impl Scanner {
+    fn new() -> Self {
+        Self {
+            state: State::Text,
+            tag: String::new(),
+            closing: false,
+            naming_tag: false,
+            attr: String::new(),
+            attr_ended: false,
+            after_lt: false,
+        }
+    }
+
+    fn open_tag(&mut self, closing: bool) {
+        self.state = State::Tag;
+        self.tag.clear();
+        self.closing = closing;
+        self.naming_tag = true;
+        self.attr.clear();
+        self.attr_ended = false;
+    }
+
+    fn end_tag(&mut self) {
+        self.state = match (self.closing, self.tag.as_str()) {
+            (false, "script") => State::Script,
+            (false, "style") => State::Style,
+            _ => State::Text,
+        };
+    }
+
+    fn feed(&mut self, text: &str) {
+        let bytes = text.as_bytes();
+        let mut i = 0;
+        while let Some(&c) = bytes.get(i) {
+            let rest = &bytes[i..];
+            match self.state {
+                State::Text if rest.starts_with(b"<!--") => {
+                    self.state = State::Comment;
+                    i += 4;
+                    continue;
+                }
+                State::Text if c == b'<' => match rest.get(1) {
+                    Some(b'/') => {
+                        self.open_tag(true);
+                        i += 2;
+                        continue;
+                    }
+                    Some(next) if next.is_ascii_alphabetic() => self.open_tag(false),
+                    _ => {}
+                },
+                State::Text => {}
+                State::Comment => {
+                    if rest.starts_with(b"-->") {
+                        self.state = State::Text;
+                        i += 3;
+                        continue;
+                    }
+                }
+                State::Script | State::Style => {
+                    let end: &[u8] = if self.state == State::Script {
+                        b"</script"
+                    } else {
+                        b"</style"
+                    };
+                    if starts_with_ignore_case(rest, end) {
+                        self.open_tag(true);
+                        self.tag = String::from_utf8_lossy(&end[2..]).into_owned();
+                        self.naming_tag = false;
+                        i += end.len();
+                        continue;
+                    }
+                }
+                State::Tag => match c {
+                    b'>' => self.end_tag(),
+                    b'=' => self.state = State::BeforeValue,
+                    b'/' => self.naming_tag = false,
+                    c if c.is_ascii_whitespace() => {
+                        self.naming_tag = false;
+                        self.attr_ended = true;
+                    }
+                    c if self.naming_tag => self.tag.push(c.to_ascii_lowercase() as char),
+                    c => {
+                        if self.attr_ended {
+                            self.attr.clear();
+                            self.attr_ended = false;
+                        }
+                        self.attr.push(c.to_ascii_lowercase() as char);
+                    }
+                },
+                State::BeforeValue => match c {
+                    b'"' | b'\'' => self.state = State::Attribute { quote: c },
+                    b'>' => self.end_tag(),
+                    c if c.is_ascii_whitespace() => {}
+                    _ => self.state = State::Unquoted,
+                },
+                State::Attribute { quote } => {
+                    if c == quote {
+                        self.state = State::Tag;
+                        self.attr_ended = true;
+                    }
+                }
+                State::Unquoted => match c {
+                    b'>' => self.end_tag(),
+                    c if c.is_ascii_whitespace() => {
+                        self.state = State::Tag;
+                        self.attr_ended = true;
+                    }
+                    _ => {}
+                },
+            }
+            i += 1;
+        }
+        if let Some(&last) = bytes.last() {
+            self.after_lt = last == b'<' && self.state == State::Text;
+        }
+    }
+
+    /// Whether the HTML context is the same as at `other`
+    fn same_context(&self, other: &Self) -> bool {
+        self.state == other.state && self.escape() == other.escape()
+    }
+
+    /// How a value inserted here is escaped, `None` where values aren't allowed
+    fn escape(&self) -> Option<Escape> {
+        match self.state {
+            State::Text if self.after_lt => None,
+            State::Text | State::Comment => Some(Escape::Text),
+            State::Attribute { .. } if self.attr.starts_with("on") => Some(Escape::Script),
+            State::Attribute { .. } => Some(Escape::Attribute),
+            State::Script => Some(Escape::Script),
+            State::Tag | State::BeforeValue | State::Unquoted | State::Style => None,
+        }
+    }
+}
+
+/// A block being parsed
+enum Block {
+    For {
+        var: String,
+        path: Vec<String>,
+    },
+    If {
+        negate: bool,
+        path: Vec<String>,
+        then: Option<Vec<Node>>,
+    },
+}
+
+fn parse_path(path: &str) -> Option<Vec<String>> {
+    let path = path
+        .split('.')
+        .map(|part| {
+            (!part.is_empty() && part.chars().all(|c| c.is_alphanumeric() || c == '_'))
+                .then(|| part.to_owned())
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(path)
+}
+
+/// A page with `{{ value }}`s, `{% for item in list %}`/`{% endfor %}` loops and
+/// `{% if [not] value %}`/`{% else %}`/`{% endif %}` conditionals
+///
+/// Values are escaped for where they are in the HTML (element content, quoted attribute
+/// values, or string literals in scripts and event handlers), elsewhere they aren't allowed.
+#[derive(Debug)]
+pub struct Template {
+    nodes: Vec<Node>,
+}
+
+impl Template {
+    pub fn parse(src: &str) -> Result<Self, TemplateError> {
+        let error = |pos: usize, message: &str| TemplateError {
+            line: src[..pos].matches('\n').count() + 1,
+            message: message.to_owned(),
+        };
+        let mut scanner = Scanner::new();
+        // Blocks being parsed, with the nodes before them and the HTML context they start in
+        let mut blocks: Vec<(Block, Vec<Node>, Scanner)> = vec![];
+        let mut nodes = vec![];
+        let mut pos = 0;
+        while let Some(start) = src[pos..].find('{').map(|start| pos + start) {
+            let close = match src.get(start..start + 2) {
+                Some("{{") => "}}",
+                Some("{%") => "%}",
+                _ => {
+                    scanner.feed(&src[pos..start + 1]);
+                    nodes.push(Node::Text(src[pos..start + 1].to_owned()));
+                    pos = start + 1;
+                    continue;
+                }
+            };
+            scanner.feed(&src[pos..start]);
+            nodes.push(Node::Text(src[pos..start].to_owned()));
+            let end = src[start..]
+                .find(close)
+                .map(|end| start + end)
+                .ok_or_else(|| error(start, "unclosed tag"))?;
+            let inner = src[start + 2..end].trim();
+            pos = end + 2;
+
+            if close == "}}" {
+                let path = parse_path(inner).ok_or_else(|| error(start, "invalid value"))?;
+                let escape = scanner
+                    .escape()
+                    .ok_or_else(|| error(start, "values aren't allowed here"))?;
+                nodes.push(Node::Value { path, escape });
+                continue;
+            }
+            let words = inner.split_whitespace().collect::<Vec<_>>();
+            let invalid_path = || error(start, "invalid value");
+            match words[..] {
+                ["for", var, "in", path] => {
+                    let block = Block::For {
+                        var: var.to_owned(),
+                        path: parse_path(path).ok_or_else(invalid_path)?,
+                    };
+                    blocks.push((block, take(&mut nodes), scanner.clone()));
+                }
+                ["if", path] | ["if", "not", path] => {
+                    let block = Block::If {
+                        negate: words.len() == 3,
+                        path: parse_path(path).ok_or_else(invalid_path)?,
+                        then: None,
+                    };
+                    blocks.push((block, take(&mut nodes), scanner.clone()));
+                }
+                ["else"] | ["endif"] | ["endfor"] => {
+                    let Some((block, before, start_scanner)) = blocks.pop() else {
+                        return Err(error(start, "no block to end"));
+                    };
+                    // Otherwise escaping after the block would depend on the data
+                    if !scanner.same_context(&start_scanner) {
+                        return Err(error(start, "block changes the HTML context"));
+                    }
+                    match (words[0], block) {
+                        (
+                            "else",
+                            Block::If {
+                                negate,
+                                path,
+                                then: None,
+                            },
+                        ) => {
+                            let then = Some(take(&mut nodes));
+                            blocks.push((Block::If { negate, path, then }, before, start_scanner));
+                        }
+                        ("endif", Block::If { negate, path, then }) => {
+                            let (then, otherwise) = match then {
+                                Some(then) => (then, take(&mut nodes)),
+                                None => (take(&mut nodes), vec![]),
+                            };
+                            nodes = before;
+                            nodes.push(Node::If {
+                                negate,
+                                path,
+                                then,
+                                otherwise,
+                            });
+                        }
+                        ("endfor", Block::For { var, path }) => {
+                            let body = take(&mut nodes);
+                            nodes = before;
+                            nodes.push(Node::For { var, path, body });
+                        }
+                        _ => return Err(error(start, "mismatched block end")),
+                    }
+                }
+                _ => return Err(error(start, "unknown tag")),
+            }
+        }
+        nodes.push(Node::Text(src[pos..].to_owned()));
+        if !blocks.is_empty() {
+            return Err(error(src.len(), "unclosed block"));
+        }
+        Ok(Self { nodes })
+    }
+
+    /// Render with `context`, values missing from it are empty
+    pub fn render(&self, context: &Value) -> String {
+        let mut out = String::new();
+        render_nodes(&self.nodes, context, &mut vec![], &mut out);
+        out
+    }
+}
+
+fn lookup<'a>(path: &[String], root: &'a Value, scopes: &[(&str, &'a Value)]) -> Option<&'a Value> {
+    let (first, rest) = path.split_first()?;
+    let mut value = match scopes.iter().rev().find(|(name, _)| name == first) {
+        Some((_, value)) => *value,
+        None => match root {
+            Value::Map(entries) => entries.get(first)?,
+            _ => return None,
+        },
+    };
+    for part in rest {
+        let Value::Map(entries) = value else {
+            return None;
+        };
+        value = entries.get(part)?;
+    }
+    Some(value)
+}
+
+fn render_nodes<'a>(
+    nodes: &'a [Node],
+    root: &'a Value,
+    scopes: &mut Vec<(&'a str, &'a Value)>,
+    out: &mut String,
+) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Value { path, escape } => match lookup(path, root, scopes) {
+                Some(Value::Text(text)) => push_escaped(out, text, *escape, false),
+                Some(Value::Html(html)) => push_escaped(out, html, *escape, true),
+                Some(Value::Bool(value)) => out.push_str(if *value { "true" } else { "false" }),
+                _ => {}
+            },
+            Node::For { var, path, body } => {
+                let Some(Value::List(items)) = lookup(path, root, scopes) else {
+                    continue;
+                };
+                for item in items {
+                    scopes.push((var, item));
+                    render_nodes(body, root, scopes, out);
+                    scopes.pop();
+                }
+            }
+            Node::If {
+                negate,
+                path,
+                then,
+                otherwise,
+            } => {
+                let value = lookup(path, root, scopes).is_some_and(Value::is_truthy);
+                let branch = if value != *negate { then } else { otherwise };
+                render_nodes(branch, root, scopes, out);
+            }
+        }
+    }
+}
+
+fn push_escaped(out: &mut String, text: &str, escape: Escape, is_html: bool) {
+    for c in text.chars() {
+        match (escape, c) {
+            // Cleaned markup keeps its tags and entities in element content
+            (Escape::Text, _) if is_html => out.push(c),
+            (Escape::Text | Escape::Attribute, '&') if !is_html => out.push_str("&amp;"),
+            (Escape::Text | Escape::Attribute, '<') => out.push_str("&lt;"),
+            (Escape::Text | Escape::Attribute, '>') => out.push_str("&gt;"),
+            (Escape::Text | Escape::Attribute, '"') => out.push_str("&quot;"),
+            (Escape::Text | Escape::Attribute, '\'') => out.push_str("&#39;"),
+            (Escape::Text | Escape::Attribute, c) => out.push(c),
+            // Escaping everything else keeps the HTML around event handlers intact as well
+            (Escape::Script, c) if c.is_ascii_alphanumeric() || " _-.,:/".contains(c) => {
+                out.push(c)
+            }
+            (Escape::Script, c) => {
+                for unit in c.encode_utf16(&mut [0; 2]) {
+                    out.push_str(&format!("\\u{unit:04x}"));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(src: &str, context: Value) -> String {
+        Template::parse(src).unwrap().render(&context)
+    }
+
+    fn parse_error(src: &str) -> String {
+        Template::parse(src).unwrap_err().message
+    }
+
+    #[test]
+    fn escapes_text() {
+        let context = Value::map([("name", "<b>\"Tom\" & 'Jerry'</b>".into())]);
+        assert_eq!(
+            render("<p>{{ name }}</p>", context),
+            "<p>&lt;b&gt;&quot;Tom&quot; &amp; &#39;Jerry&#39;&lt;/b&gt;</p>"
+        );
+    }
+
+    #[test]
+    fn keeps_cleaned_html_in_text_only() {
+        let context = Value::map([("bio", Value::Html("<b>a &amp; b</b>".to_owned()))]);
+        assert_eq!(
+            render("<p>{{ bio }}</p>", context.clone()),
+            "<p><b>a &amp; b</b></p>"
+        );
+        assert_eq!(
+            render("<p title=\"{{ bio }}\">", context),
+            "<p title=\"&lt;b&gt;a &amp; b&lt;/b&gt;\">"
+        );
+    }
+
+    #[test]
+    fn escapes_attributes() {
+        let context = Value::map([("url", "\" onload='x'".into())]);
+        assert_eq!(
+            render("<a href=\"{{ url }}\">", context.clone()),
+            "<a href=\"&quot; onload=&#39;x&#39;\">"
+        );
+        assert_eq!(
+            render("<a href='{{ url }}'>", context),
+            "<a href='&quot; onload=&#39;x&#39;'>"
+        );
+    }
+
+    #[test]
+    fn escapes_event_handlers_and_scripts() {
+        let context = Value::map([("id", "a'); alert(\"x".into())]);
+        let escaped = "a\\u0027\\u0029\\u003b alert\\u0028\\u0022x";
+        assert_eq!(
+            render("<button onclick=\"play('{{ id }}')\">", context.clone()),
+            format!("<button onclick=\"play('{escaped}')\">")
+        );
+        assert_eq!(
+            render("<button ONCLICK='play(\"{{ id }}\")'>", context.clone()),
+            format!("<button ONCLICK='play(\"{escaped}\")'>")
+        );
+        assert_eq!(
+            render("<script>play(\"{{ id }}\")</script>", context.clone()),
+            format!("<script>play(\"{escaped}\")</script>")
+        );
+        let context = Value::map([("id", "</script><b>".into())]);
+        assert_eq!(
+            render("<script>\"{{ id }}\"</script>{{ id }}", context),
+            "<script>\"\\u003c/script\\u003e\\u003cb\\u003e\"</script>&lt;/script&gt;&lt;b&gt;"
+        );
+    }
+
+    #[test]
+    fn rejects_values_outside_text_and_quoted_attributes() {
+        for src in [
+            "<a href={{ url }}>",
+            "<a href=x{{ url }}>",
+            "<a {{ attr }}>",
+            "<{{ tag }}>",
+            "<a href=\"x\" {{ attr }}>",
+            "<style>{{ css }}</style>",
+        ] {
+            assert_eq!(parse_error(src), "values aren't allowed here", "{src}");
+        }
+        // After the tag ends, values are text again
+        assert_eq!(
+            render("<a href=x>{{ url }}</a>", Value::map([("url", "<".into())])),
+            "<a href=x>&lt;</a>"
+        );
+    }
+
+    #[test]
+    fn rejects_blocks_changing_the_context() {
+        for src in [
+            "{% if a %}<a href=\"{% endif %}\">",
+            "<p {% if a %}title=\"x{% else %}{% endif %}\">",
+            "{% for a in b %}<script>{% endfor %}</script>",
+        ] {
+            assert_eq!(parse_error(src), "block changes the HTML context", "{src}");
+        }
+        assert!(Template::parse("<a {% if a %}title=\"x\"{% endif %}>").is_ok());
+    }
+
+    #[test]
+    fn reports_the_line_of_errors() {
+        let error = Template::parse("<p>\n{% endif %}").unwrap_err();
+        assert_eq!((error.line, error.message.as_str()), (2, "no block to end"));
+        assert_eq!(parse_error("{% if a %}"), "unclosed block");
+        assert_eq!(
+            parse_error("{% for a in b %}{% endif %}"),
+            "mismatched block end"
+        );
+        assert_eq!(parse_error("{{ a }"), "unclosed tag");
+        assert_eq!(parse_error("{{ a b }}"), "invalid value");
+        assert_eq!(parse_error("{% while a %}"), "unknown tag");
+    }
+
+    #[test]
+    fn renders_loops() {
+        let context = Value::map([
+            ("title", "T".into()),
+            (
+                "songs",
+                vec![
+                    Value::map([("name", "a".into())]),
+                    Value::map([("name", "<b>".into())]),
+                ]
+                .into(),
+            ),
+        ]);
+        assert_eq!(
+            render(
+                "<ul>{% for song in songs %}<li>{{ title }}: {{ song.name }}</li>{% endfor %}</ul>",
+                context
+            ),
+            "<ul><li>T: a</li><li>T: &lt;b&gt;</li></ul>"
+        );
+        assert_eq!(
+            render("{% for a in missing %}x{% endfor %}", Value::map([])),
+            ""
+        );
+    }
+
+    #[test]
+    fn renders_conditionals() {
+        let src = "{% if a %}yes{% else %}no{% endif %}|{% if not a %}!{% endif %}";
+        for (value, expected) in [
+            (Value::Bool(true), "yes|"),
+            (Value::Bool(false), "no|!"),
+            ("x".into(), "yes|"),
+            ("".into(), "no|!"),
+            (Value::List(vec![]), "no|!"),
+            (Value::List(vec![Value::Bool(false)]), "yes|"),
+            (Value::map([("b", true.into())]), "yes|"),
+        ] {
+            assert_eq!(
+                render(src, Value::map([("a", value.clone())])),
+                expected,
+                "{value:?}"
+            );
+        }
+        assert_eq!(render(src, Value::map([])), "no|!");
+        let context = Value::map([("user", Value::map([("admin", true.into())]))]);
+        assert_eq!(
+            render("{% if user.admin %}admin{% endif %}", context),
+            "admin"
+        );
+    }
+
+    #[test]
+    fn leaves_other_braces_alone() {
+        assert_eq!(
+            render("<script>if (a) { b({}); }</script>", Value::map([])),
+            "<script>if (a) { b({}); }</script>"
+        );
+    }
+}
@@ -0,0 +1,99 @@
+use std::{
+    collections::VecDeque,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::watch;
+
+use crate::{blocking::Segment, listeners::unix_now};
+
+/// Directory of the time-shift archives, inside the data directory
+pub const TIMESHIFT_DIR: &str = "timeshift";
+
+#[derive(Debug)]
+struct ArchiveInner {
+    dir: PathBuf,
+    /// Seconds of segments kept, nothing is archived if 0
+    window: u64,
+    /// Sequence number and publish time of the archived segments, oldest first
+    index: Mutex<VecDeque<(u64, u64)>>,
+}
+
+/// The segments a radio published recently, for listening back in time
+/// (cheap to clone, shared between handlers)
+///
+/// Archives live on the local disk and don't survive restarts.
+#[derive(Debug, Clone)]
+pub struct Archive(Arc<ArchiveInner>);
+
+impl Archive {
+    pub fn new(dir: PathBuf, window: u64) -> Self {
+        Self(Arc::new(ArchiveInner {
+            dir,
+            window,
+            index: Mutex::new(VecDeque::new()),
+        }))
+    }
+
+    fn path(&self, seq: u64) -> PathBuf {
+        self.0.dir.join(format!("{seq}.aac"))
+    }
+
+    /// Sequence number of the first segment published from `since` (unix time) on
+    pub fn first_since(&self, since: u64) -> Option<u64> {
+        self.0
+            .index
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(_, time)| *time >= since)
+            .map(|&(seq, _)| seq)
+    }
+
+    /// Sequence number and file of the first archived segment from `seq` on
+    pub fn next(&self, seq: u64) -> Option<(u64, PathBuf)> {
+        self.0
+            .index
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(archived, _)| *archived >= seq)
+            .map(|&(seq, _)| (seq, self.path(seq)))
+    }
+}
+
+/// Archive the segments of a radio as they are published, finishes when the radio is removed
+pub async fn archive_task(mut stream: watch::Receiver<Segment>, archive: Archive) {
+    if archive.0.window == 0 {
+        return;
+    }
+    if let Err(e) = tokio::fs::create_dir_all(&archive.0.dir).await {
+        eprintln!("Couldn't create archive {}: {e}", archive.0.dir.display());
+        return;
+    }
+    while stream.changed().await.is_ok() {
+        let (seq, data) = {
+            let segment = stream.borrow_and_update();
+            (segment.seq, segment.data.clone())
+        };
+        if let Err(e) = tokio::fs::write(archive.path(seq), data).await {
+            eprintln!("Couldn't archive segment {seq}: {e}");
+            continue;
+        }
+        let now = unix_now();
+        let expired = {
+            let mut index = archive.0.index.lock().unwrap();
+            index.push_back((seq, now));
+            let keep = index
+                .iter()
+                .position(|(_, time)| now.saturating_sub(*time) <= archive.0.window)
+                .unwrap_or(index.len());
+            index.drain(..keep).collect::<Vec<_>>()
+        };
+        for (seq, _) in expired {
+            let _ = tokio::fs::remove_file(archive.path(seq)).await;
+        }
+    }
+    archive.0.index.lock().unwrap().clear();
+    let _ = tokio::fs::remove_dir_all(&archive.0.dir).await;
+}
@@ -0,0 +1,121 @@
+use base64::Engine;
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use crate::{listeners::unix_now, quotas::Usage};
+
+/// Version of the tus protocol spoken by the upload endpoints
+pub const TUS_VERSION: &str = "1.0.0";
+/// Extensions of the tus protocol supported
+pub const TUS_EXTENSIONS: &str = "creation,termination";
+/// Seconds after which an untouched upload is dropped
+const UPLOAD_EXPIRY: u64 = 24 * 60 * 60;
+
+/// A resumable upload of a song, not yet complete
+#[derive(Debug)]
+pub struct PendingUpload {
+    pub radio: String,
+    /// Name of the song once uploaded
+    pub name: String,
+    /// Size of the whole file in bytes
+    pub length: u64,
+    /// Bytes received so far
+    pub offset: u64,
+    /// The received part of the file
+    pub path: PathBuf,
+    /// When a chunk was last received
+    pub touched: u64,
+}
+
+#[derive(Debug, Default)]
+struct UploadsInner {
+    uploads: HashMap<String, Arc<tokio::sync::Mutex<PendingUpload>>>,
+    /// Radio and length of each upload, readable while a chunk is received
+    reserved: HashMap<String, (String, u64)>,
+}
+
+/// Resumable uploads in progress, by upload id
+#[derive(Debug, Default)]
+pub struct Uploads {
+    inner: Mutex<UploadsInner>,
+}
+
+impl Uploads {
+    /// Register an upload, returning its id
+    pub fn create(&self, upload: PendingUpload) -> String {
+        let id = format!("{:032x}", rand::random::<u128>());
+        let mut inner = self.inner.lock().unwrap();
+        inner
+            .reserved
+            .insert(id.clone(), (upload.radio.clone(), upload.length));
+        inner
+            .uploads
+            .insert(id.clone(), Arc::new(tokio::sync::Mutex::new(upload)));
+        id
+    }
+
+    pub fn get(&self, id: &str) -> Option<Arc<tokio::sync::Mutex<PendingUpload>>> {
+        self.inner.lock().unwrap().uploads.get(id).cloned()
+    }
+
+    pub fn remove(&self, id: &str) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.uploads.remove(id);
+        inner.reserved.remove(id);
+    }
+
+    /// Count the uploads in progress to the radios of `usage` in, as songs of their
+    /// length, so uploads in parallel can't exceed the quotas
+    pub fn reserve(&self, usage: &mut Usage) {
+        for (radio, length) in self.inner.lock().unwrap().reserved.values() {
+            if let Some(songs) = usage.songs.get_mut(radio) {
+                *songs += 1;
+                usage.bytes += length;
+            }
+        }
+    }
+
+    /// Drop uploads untouched for too long along with their files
+    pub fn expire(&self) {
+        let now = unix_now();
+        let mut inner = self.inner.lock().unwrap();
+        let UploadsInner { uploads, reserved } = &mut *inner;
+        uploads.retain(|id, upload| {
+            // Uploads receiving a chunk right now aren't expired
+            let Ok(upload) = upload.try_lock() else {
+                return true;
+            };
+            if now.saturating_sub(upload.touched) < UPLOAD_EXPIRY {
+                return true;
+            }
+            eprintln!("Upload {id} of {} expired", upload.name);
+            if let Err(e) = std::fs::remove_file(&upload.path) {
+                eprintln!("Couldn't remove expired upload {id}: {e}");
+            }
+            reserved.remove(id);
+            false
+        });
+    }
+}
+
+/// Parse an `Upload-Metadata` header, pairs of a key and a base64 value separated by commas
+pub fn parse_metadata(header: &str) -> HashMap<String, String> {
+    header
+        .split(',')
+        .filter_map(|pair| {
+            let mut parts = pair.trim().splitn(2, ' ');
+            let key = parts.next().filter(|key| !key.is_empty())?;
+            let value = match parts.next() {
+                Some(value) => base64::engine::general_purpose::STANDARD
+                    .decode(value.trim())
+                    .ok()
+                    .and_then(|value| String::from_utf8(value).ok())?,
+                None => String::new(),
+            };
+            Some((key.to_owned(), value))
+        })
+        .collect()
+}